@@ -1,23 +1,14 @@
-use elliptic_curve::pkcs8::DecodePublicKey;
-use opacity::read_env_vars;
-use reqwest::ClientBuilder;
-use reqwest::Error;
-use serde::{Deserialize, Serialize};
-use std::{str, time::Duration};
-use tlsn_core::proof::{SessionProof, TlsProof};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct InfoResponse {
-    /// Current version of notary-server
-    pub version: String,
-    /// Public key of the notary signing key
-    pub public_key: String,
-    /// Current git commit hash of notary-server
-    pub git_commit_hash: String,
-    /// Current git commit timestamp of notary-server
-    pub git_commit_timestamp: String,
+use opacity::{verify, NotaryConfig, ProofEnvelope, TrustedNotaries, VerificationPolicy};
+
+/// What the proof generated by `simple_prover.rs` is expected to attest to.
+const POLICY_STR: &str = r###"
+{
+    "allowed_servers":["trading-api.kalshi.com"],
+    "max_age_secs":86400,
+    "method":"GET",
+    "path":"/trade-api/v2/exchange/schedule"
 }
+"###;
 
 /// A simple verifier which reads a proof generated by `simple_prover.rs` from "proof.json", verifies
 /// it and prints the verified data to the console.
@@ -25,83 +16,36 @@ pub struct InfoResponse {
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let (notary_host, notary_port) = read_env_vars();
-    let notary_public_key = notary_pubkey(notary_host, notary_port).await.unwrap();
-    // Deserialize the proof
-    let proof = std::fs::read_to_string("simple_proof.json").unwrap();
-    let proof: TlsProof = serde_json::from_str(proof.as_str()).unwrap();
-
-    let TlsProof {
-        // The session proof establishes the identity of the server and the commitments
-        // to the TLS transcript.
-        session,
-        // The substrings proof proves select portions of the transcript, while redacting
-        // anything the Prover chose not to disclose.
-        substrings,
-    } = proof;
-
-    // Verify the session proof against the Notary's public key
-    //
-    // This verifies the identity of the server using a default certificate verifier which trusts
-    // the root certificates from the `webpki-roots` crate.
-    session
-        .verify_with_default_cert_verifier(notary_public_key)
-        .unwrap();
+    let config = NotaryConfig::from_env().unwrap();
+    let trusted_notaries = TrustedNotaries::from_config(&config).await.unwrap();
+    // Deserialize the proof envelope, whichever format it was written in
+    let envelope = ProofEnvelope::read("simple_proof.json").unwrap();
 
-    let SessionProof {
-        // The session header that was signed by the Notary is a succinct commitment to the TLS transcript.
-        header,
-        // This is the session_info, which contains the server_name, that is checked against the
-        // certificate chain shared in the TLS handshake.
-        session_info,
-        ..
-    } = session;
+    let presentation = verify(envelope.proof, &trusted_notaries).unwrap();
 
-    // The time at which the session was recorded
-    let time = chrono::DateTime::UNIX_EPOCH + Duration::from_secs(header.time());
-
-    // Verify the substrings proof against the session header.
-    //
-    // This returns the redacted transcripts
-    let (mut sent, mut recv) = substrings.verify(&header).unwrap();
-
-    // Replace the bytes which the Prover chose not to disclose with 'X'
-    sent.set_redacted(b'X');
-    recv.set_redacted(b'X');
+    let policy: VerificationPolicy = serde_json::from_str(POLICY_STR).unwrap();
+    let report = policy.evaluate(&presentation);
+    for failure in report.failures() {
+        println!(
+            "Policy rule {:?} failed: {:?}",
+            failure.rule, failure.reason
+        );
+    }
+    assert!(report.passed(), "the proof does not satisfy the policy");
 
     println!("-------------------------------------------------------------------");
     println!(
         "Successfully verified that the bytes below came from a session with {:?} at {}.",
-        session_info.server_name, time
+        presentation.server_name, presentation.time
     );
     println!("Note that the bytes which the Prover chose not to disclose are shown as X.");
     println!();
     println!("Bytes sent:");
     println!();
-    print!("{}", String::from_utf8(sent.data().to_vec()).unwrap());
+    print!("{}", presentation.sent.to_string_lossy(b'X'));
     println!();
     println!("Bytes received:");
     println!();
-    println!("{}", String::from_utf8(recv.data().to_vec()).unwrap());
+    println!("{}", presentation.recv.to_string_lossy(b'X'));
     println!("-------------------------------------------------------------------");
 }
-
-/// Returns a Notary pubkey trusted by this Verifier
-// async fn notary_pubkey() -> p256::PublicKey {
-async fn notary_pubkey(notary_host: String, notary_port: u16) -> Result<p256::PublicKey, Error> {
-    let url = format!("https://{}:{}/info", notary_host, notary_port);
-
-    // Make the request
-    let client = ClientBuilder::new()
-        .danger_accept_invalid_certs(true)
-        .build()
-        .unwrap();
-    let response = client.get(url).send().await?;
-
-    // Parse the response body as JSON into the ApiResponse struct
-    let info_response: InfoResponse = response.json().await?;
-
-    let public_key = p256::PublicKey::from_public_key_pem(&info_response.public_key).unwrap();
-
-    Ok(public_key)
-}
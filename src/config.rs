@@ -0,0 +1,631 @@
+use crate::{
+    retry::{RetryClass, RetryPolicy},
+    trust::TrustStore,
+};
+use std::{
+    collections::BTreeMap,
+    env, fmt, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Default location of the Opacity notary CA, relative to the working directory.
+pub const DEFAULT_CA_PATH: &str = "fixture/opacityCA.crt";
+/// Default maximum number of bytes the prover may send to the server.
+pub const DEFAULT_MAX_SENT_DATA: usize = 1 << 13;
+/// Default maximum number of bytes the prover may receive from the server.
+pub const DEFAULT_MAX_RECV_DATA: usize = 1 << 13;
+/// Default upper bound of the receive budget picked by a preflight request.
+pub const DEFAULT_MAX_RECV_DATA_CEILING: usize = 1 << 16;
+/// Default timeout for opening the TCP connection to the server.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Default timeout for the notary to accept a notarization request.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Default time during which a notary which failed is tried after the others.
+pub const DEFAULT_UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(30);
+/// Priority of the notaries listed in [`NotaryConfig::notaries`] which do not set one.
+pub const DEFAULT_FALLBACK_PRIORITY: u32 = 1;
+
+/// Prefix prepended to every key when it is read from the environment or a `.env` file.
+const ENV_PREFIX: &str = "NOTARY_";
+
+/// Every key understood by [`NotaryConfig`], as spelled in TOML files and CLI overrides.
+///
+/// In the environment they are upper-cased and prefixed with `NOTARY_`, e.g. `NOTARY_HOST`.
+pub const CONFIG_KEYS: &[&str] = &[
+    "host",
+    "port",
+    "ca_path",
+    "trust_store",
+    "tls",
+    "public_key",
+    "public_key_path",
+    "fetch_public_key",
+    "max_sent_data",
+    "max_recv_data",
+    "preflight",
+    "max_recv_data_ceiling",
+    "connect_timeout_secs",
+    "request_timeout_secs",
+    "notaries",
+    "health_check",
+    "unhealthy_cooldown_secs",
+    "retry_max_attempts",
+    "retry_initial_backoff_ms",
+    "retry_max_backoff_ms",
+    "retry_jitter_percent",
+    "retry_on",
+];
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The process environment.
+    Env,
+    /// A `.env` file.
+    DotEnv,
+    /// A TOML configuration file.
+    File(PathBuf),
+    /// An explicit override, usually passed on the command line.
+    Override,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Env => write!(f, "environment"),
+            ConfigSource::DotEnv => write!(f, ".env file"),
+            ConfigSource::File(path) => write!(f, "config file {}", path.display()),
+            ConfigSource::Override => write!(f, "override"),
+        }
+    }
+}
+
+/// Errors raised while loading a [`NotaryConfig`].
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("missing required config key `{key}` (set $NOTARY_{} or `{key}` in the config file)", .key.to_uppercase())]
+    Missing { key: &'static str },
+    #[error("invalid value {value:?} for config key `{key}` from {origin}: {reason}")]
+    Invalid {
+        key: &'static str,
+        value: String,
+        origin: ConfigSource,
+        reason: String,
+    },
+    #[error("unknown config key `{key}` from {origin}")]
+    UnknownKey { key: String, origin: ConfigSource },
+    #[error("malformed override {0:?}, expected `key=value`")]
+    MalformedOverride(String),
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("failed to parse {path}: {source}")]
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("failed to parse .env file: {0}")]
+    DotEnv(#[from] dotenvy::Error),
+}
+
+/// Settings used to reach a notary and size a notarization session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotaryConfig {
+    /// Host name of the notary server.
+    pub host: String,
+    /// Port of the notary server.
+    pub port: u16,
+    /// Root certificates used to authenticate the notary's TLS certificate.
+    pub trust_store: TrustStore,
+    /// Whether to talk to the notary over TLS.
+    pub tls: bool,
+    /// PEM encoded public key(s) the notary is expected to sign with.
+    pub public_key: Option<String>,
+    /// PEM file holding the public key(s) the notary is expected to sign with.
+    pub public_key_path: Option<PathBuf>,
+    /// Whether verifiers may fetch the notary's public key from its `/info` endpoint.
+    pub fetch_public_key: bool,
+    /// Maximum number of bytes the prover may send to the server, unless the request sets its own.
+    pub max_sent_data: usize,
+    /// Maximum number of bytes the prover may receive from the server, unless the request sets
+    /// its own.
+    pub max_recv_data: usize,
+    /// Whether to size the receive budget of a session after a plain, unnotarized request to the
+    /// server, when the request does not set its own.
+    pub preflight: bool,
+    /// Upper bound of the receive budget picked by a preflight request.
+    pub max_recv_data_ceiling: usize,
+    /// Timeout for opening the TCP connection to the server.
+    pub connect_timeout: Duration,
+    /// Timeout for the notary to accept a notarization request.
+    pub request_timeout: Duration,
+    /// Other notaries to request sessions from when the one above fails. They share its trust
+    /// store, TLS setting and pinned keys.
+    pub notaries: Vec<NotaryEndpoint>,
+    /// Whether to query the `/healthcheck` endpoint of every notary before picking one, when
+    /// several are configured.
+    pub health_check: bool,
+    /// How long a notary which failed is tried after the others.
+    pub unhealthy_cooldown: Duration,
+    /// How failed notarizations are retried.
+    pub retry: RetryPolicy,
+}
+
+impl NotaryConfig {
+    /// Creates a config for the given notary with every other setting at its default.
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            trust_store: TrustStore::PemFile(PathBuf::from(DEFAULT_CA_PATH)),
+            tls: true,
+            public_key: None,
+            public_key_path: None,
+            fetch_public_key: false,
+            max_sent_data: DEFAULT_MAX_SENT_DATA,
+            max_recv_data: DEFAULT_MAX_RECV_DATA,
+            preflight: false,
+            max_recv_data_ceiling: DEFAULT_MAX_RECV_DATA_CEILING,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            notaries: Vec::new(),
+            health_check: false,
+            unhealthy_cooldown: DEFAULT_UNHEALTHY_COOLDOWN,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Returns a loader which layers `.env`, the environment, a TOML file and overrides.
+    pub fn loader() -> ConfigLoader {
+        ConfigLoader::default()
+    }
+
+    /// Loads the config from the environment and the `.env` file, if any.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::loader().load()
+    }
+
+    /// Returns the default data limits of a session.
+    pub fn data_limits(&self) -> DataLimits {
+        DataLimits {
+            max_sent_data: self.max_sent_data,
+            max_recv_data: self.max_recv_data,
+        }
+    }
+
+    /// Returns every configured notary: the one set by `host` and `port`, with priority 0,
+    /// followed by [`NotaryConfig::notaries`].
+    pub fn endpoints(&self) -> Vec<NotaryEndpoint> {
+        let primary = NotaryEndpoint {
+            host: self.host.clone(),
+            port: self.port,
+            priority: 0,
+        };
+        std::iter::once(primary)
+            .chain(self.notaries.iter().cloned())
+            .collect()
+    }
+
+    /// Returns the configured notary reached at `address`, given as `host:port`.
+    pub fn find_endpoint(&self, address: &str) -> Option<NotaryEndpoint> {
+        self.endpoints()
+            .into_iter()
+            .find(|endpoint| endpoint.address() == address)
+    }
+
+    /// Returns this config with `endpoint` as its only notary.
+    pub fn for_endpoint(&self, endpoint: &NotaryEndpoint) -> NotaryConfig {
+        NotaryConfig {
+            host: endpoint.host.clone(),
+            port: endpoint.port,
+            notaries: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+
+/// A notary sessions may be requested from.
+///
+/// Notaries are tried by increasing priority. Those sharing a priority are tried in random order,
+/// spreading sessions between them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotaryEndpoint {
+    pub host: String,
+    pub port: u16,
+    pub priority: u32,
+}
+
+impl NotaryEndpoint {
+    /// Returns the address of the notary as `host:port`.
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Parses `host:port`, optionally followed by `@priority`, the priority defaulting to
+/// [`DEFAULT_FALLBACK_PRIORITY`].
+impl FromStr for NotaryEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, priority) = match s.split_once('@') {
+            Some((address, priority)) => (
+                address,
+                priority
+                    .trim()
+                    .parse()
+                    .map_err(|err| format!("invalid priority {priority:?}: {err}"))?,
+            ),
+            None => (s, DEFAULT_FALLBACK_PRIORITY),
+        };
+        let (host, port) = address
+            .trim()
+            .rsplit_once(':')
+            .ok_or_else(|| format!("expected `host:port`, got {address:?}"))?;
+        if host.is_empty() {
+            return Err(format!("missing host in {address:?}"));
+        }
+        let port = port
+            .parse()
+            .map_err(|err| format!("invalid port {port:?}: {err}"))?;
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            priority,
+        })
+    }
+}
+
+/// Number of bytes a notarization session may carry in each direction.
+///
+/// The same limits must be requested from the notary and given to the prover, as the notary
+/// sizes its side of the MPC after them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLimits {
+    /// Maximum number of bytes the prover may send to the server.
+    pub max_sent_data: usize,
+    /// Maximum number of bytes the prover may receive from the server.
+    pub max_recv_data: usize,
+}
+
+impl Default for DataLimits {
+    fn default() -> Self {
+        Self {
+            max_sent_data: DEFAULT_MAX_SENT_DATA,
+            max_recv_data: DEFAULT_MAX_RECV_DATA,
+        }
+    }
+}
+
+/// Builds a [`NotaryConfig`] from several sources.
+///
+/// Sources are applied in the following order, each one overriding the previous ones:
+///
+/// 1. the `.env` file found in the working directory or one of its parents,
+/// 2. the process environment (`NOTARY_HOST`, `NOTARY_PORT`, ...),
+/// 3. the TOML file passed to [`ConfigLoader::file`],
+/// 4. the overrides passed to [`ConfigLoader::set`], usually taken from the command line.
+///
+/// As with most `.env` loaders, a variable set in the environment wins over the `.env` file.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    dotenv: bool,
+    dotenv_path: Option<PathBuf>,
+    file: Option<PathBuf>,
+    overrides: Vec<(String, String)>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self {
+            dotenv: true,
+            dotenv_path: None,
+            file: None,
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl ConfigLoader {
+    /// Whether to read a `.env` file. Enabled by default.
+    pub fn dotenv(mut self, enabled: bool) -> Self {
+        self.dotenv = enabled;
+        self
+    }
+
+    /// Reads the given `.env` file instead of searching the working directory and its parents.
+    /// Unlike a searched one, the file must exist.
+    pub fn dotenv_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.dotenv = true;
+        self.dotenv_path = Some(path.into());
+        self
+    }
+
+    /// Reads settings from the given TOML file.
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Overrides a single setting, taking precedence over every other source.
+    pub fn set(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    /// Overrides a setting given as `key=value`, as typically passed on the command line.
+    pub fn set_str(self, key_value: &str) -> Result<Self, ConfigError> {
+        let (key, value) = key_value
+            .split_once('=')
+            .ok_or_else(|| ConfigError::MalformedOverride(key_value.to_string()))?;
+        Ok(self.set(key.trim(), value.trim()))
+    }
+
+    /// Merges all sources and parses the result.
+    pub fn load(self) -> Result<NotaryConfig, ConfigError> {
+        let mut values = RawValues::default();
+
+        if self.dotenv {
+            let iter = match &self.dotenv_path {
+                Some(path) => dotenvy::from_path_iter(path),
+                None => dotenvy::dotenv_iter(),
+            };
+            match iter {
+                Ok(iter) => {
+                    for item in iter {
+                        let (name, value) = item?;
+                        if let Some(key) = name.strip_prefix(ENV_PREFIX) {
+                            let key = key.to_lowercase();
+                            if let Some(key) = known_key(&key) {
+                                values.insert(key, value, ConfigSource::DotEnv);
+                            }
+                        }
+                    }
+                }
+                Err(err) if err.not_found() && self.dotenv_path.is_none() => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        for key in CONFIG_KEYS {
+            if let Ok(value) = env::var(env_key(key)) {
+                values.insert(key, value, ConfigSource::Env);
+            }
+        }
+
+        if let Some(path) = &self.file {
+            read_toml(path, &mut values)?;
+        }
+
+        for (key, value) in self.overrides {
+            let known = known_key(&key).ok_or_else(|| ConfigError::UnknownKey {
+                key: key.clone(),
+                origin: ConfigSource::Override,
+            })?;
+            values.insert(known, value, ConfigSource::Override);
+        }
+
+        values.parse()
+    }
+}
+
+/// Raw string values keyed by config key, remembering which source set them.
+#[derive(Default)]
+struct RawValues(BTreeMap<&'static str, (String, ConfigSource)>);
+
+impl RawValues {
+    fn insert(&mut self, key: &'static str, value: String, source: ConfigSource) {
+        self.0.insert(key, (value, source));
+    }
+
+    fn parse(mut self) -> Result<NotaryConfig, ConfigError> {
+        let host = self.required("host", parse_non_empty)?;
+        let port = self.required("port", parse_from_str::<u16>)?;
+
+        let mut config = NotaryConfig::new(host, port);
+        let ca_path = self
+            .optional("ca_path", parse_non_empty)?
+            .unwrap_or_else(|| DEFAULT_CA_PATH.to_string());
+        config.trust_store = match self.optional("trust_store", parse_trust_store_kind)? {
+            None | Some(TrustStoreKind::File) => TrustStore::PemFile(PathBuf::from(ca_path)),
+            Some(TrustStoreKind::WebPki) => TrustStore::WebPki,
+            Some(TrustStoreKind::System) => TrustStore::System,
+        };
+        if let Some(tls) = self.optional("tls", parse_bool)? {
+            config.tls = tls;
+        }
+        config.public_key = self.optional("public_key", parse_non_empty)?;
+        config.public_key_path = self
+            .optional("public_key_path", parse_non_empty)?
+            .map(PathBuf::from);
+        if let Some(fetch_public_key) = self.optional("fetch_public_key", parse_bool)? {
+            config.fetch_public_key = fetch_public_key;
+        }
+        if let Some(max_sent_data) = self.optional("max_sent_data", parse_from_str::<usize>)? {
+            config.max_sent_data = max_sent_data;
+        }
+        if let Some(max_recv_data) = self.optional("max_recv_data", parse_from_str::<usize>)? {
+            config.max_recv_data = max_recv_data;
+        }
+        if let Some(preflight) = self.optional("preflight", parse_bool)? {
+            config.preflight = preflight;
+        }
+        if let Some(ceiling) = self.optional("max_recv_data_ceiling", parse_from_str::<usize>)? {
+            config.max_recv_data_ceiling = ceiling;
+        }
+        if let Some(secs) = self.optional("connect_timeout_secs", parse_from_str::<u64>)? {
+            config.connect_timeout = Duration::from_secs(secs);
+        }
+        if let Some(secs) = self.optional("request_timeout_secs", parse_from_str::<u64>)? {
+            config.request_timeout = Duration::from_secs(secs);
+        }
+        if let Some(notaries) = self.optional("notaries", parse_notaries)? {
+            config.notaries = notaries;
+        }
+        if let Some(health_check) = self.optional("health_check", parse_bool)? {
+            config.health_check = health_check;
+        }
+        if let Some(secs) = self.optional("unhealthy_cooldown_secs", parse_from_str::<u64>)? {
+            config.unhealthy_cooldown = Duration::from_secs(secs);
+        }
+        if let Some(max_attempts) = self.optional("retry_max_attempts", parse_attempts)? {
+            config.retry.max_attempts = max_attempts;
+        }
+        if let Some(ms) = self.optional("retry_initial_backoff_ms", parse_from_str::<u64>)? {
+            config.retry.initial_backoff = Duration::from_millis(ms);
+        }
+        if let Some(ms) = self.optional("retry_max_backoff_ms", parse_from_str::<u64>)? {
+            config.retry.max_backoff = Duration::from_millis(ms);
+        }
+        if let Some(percent) = self.optional("retry_jitter_percent", parse_percent)? {
+            config.retry.jitter_percent = percent;
+        }
+        if let Some(retry_on) = self.optional("retry_on", parse_retry_classes)? {
+            config.retry.retry_on = retry_on;
+        }
+
+        Ok(config)
+    }
+
+    fn required<T>(
+        &mut self,
+        key: &'static str,
+        parse: fn(&str) -> Result<T, String>,
+    ) -> Result<T, ConfigError> {
+        self.optional(key, parse)?
+            .ok_or(ConfigError::Missing { key })
+    }
+
+    fn optional<T>(
+        &mut self,
+        key: &'static str,
+        parse: fn(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, ConfigError> {
+        let Some((value, origin)) = self.0.remove(key) else {
+            return Ok(None);
+        };
+        parse(value.trim())
+            .map(Some)
+            .map_err(|reason| ConfigError::Invalid {
+                key,
+                value,
+                origin,
+                reason,
+            })
+    }
+}
+
+fn read_toml(path: &Path, values: &mut RawValues) -> Result<(), ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let table: toml::Table = contents.parse().map_err(|source| ConfigError::Toml {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let origin = ConfigSource::File(path.to_path_buf());
+    for (key, value) in table {
+        let Some(known) = known_key(&key) else {
+            return Err(ConfigError::UnknownKey { key, origin });
+        };
+        let value = match value {
+            toml::Value::String(value) => value,
+            // Lists of strings, such as `notaries`, are read as comma separated values
+            toml::Value::Array(items) if items.iter().all(toml::Value::is_str) => items
+                .iter()
+                .filter_map(toml::Value::as_str)
+                .collect::<Vec<_>>()
+                .join(","),
+            other => other.to_string(),
+        };
+        values.insert(known, value, origin.clone());
+    }
+
+    Ok(())
+}
+
+fn known_key(key: &str) -> Option<&'static str> {
+    CONFIG_KEYS.iter().copied().find(|known| *known == key)
+}
+
+fn env_key(key: &str) -> String {
+    format!("{ENV_PREFIX}{}", key.to_uppercase())
+}
+
+fn parse_non_empty(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    Ok(value.to_string())
+}
+
+fn parse_from_str<T>(value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    value.parse::<T>().map_err(|err| err.to_string())
+}
+
+/// Values accepted by the `trust_store` key.
+enum TrustStoreKind {
+    /// The PEM bundle at `ca_path`.
+    File,
+    WebPki,
+    System,
+}
+
+fn parse_trust_store_kind(value: &str) -> Result<TrustStoreKind, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "file" => Ok(TrustStoreKind::File),
+        "webpki" => Ok(TrustStoreKind::WebPki),
+        "system" => Ok(TrustStoreKind::System),
+        _ => Err("expected one of `file`, `webpki` or `system`".to_string()),
+    }
+}
+
+/// Parses a comma separated list of [`NotaryEndpoint`]s.
+fn parse_notaries(value: &str) -> Result<Vec<NotaryEndpoint>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty())
+        .map(NotaryEndpoint::from_str)
+        .collect()
+}
+
+fn parse_attempts(value: &str) -> Result<u32, String> {
+    match parse_from_str::<u32>(value)? {
+        0 => Err("must be at least 1".to_string()),
+        attempts => Ok(attempts),
+    }
+}
+
+fn parse_percent(value: &str) -> Result<u32, String> {
+    match parse_from_str::<u32>(value)? {
+        percent @ 0..=100 => Ok(percent),
+        _ => Err("must be between 0 and 100".to_string()),
+    }
+}
+
+/// Parses a comma separated list of [`RetryClass`]es, which may be empty to retry nothing.
+fn parse_retry_classes(value: &str) -> Result<Vec<RetryClass>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|class| !class.is_empty())
+        .map(RetryClass::from_str)
+        .collect()
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err("expected a boolean".to_string()),
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{
+    http::{HttpMessage, HttpParseError},
+    json::{JsonError, JsonValue},
+    range::RangeSet,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json_path::JsonPath;
+
+/// Errors raised while selecting the disclosed parts of a received JSON body.
+#[derive(Debug, thiserror::Error)]
+pub enum DisclosureError {
+    #[error("cannot parse the received transcript as HTTP: {0}")]
+    Http(#[from] HttpParseError),
+    #[error("cannot disclose fields of the received body: {0}")]
+    Json(#[from] JsonError),
+    #[error("invalid JSONPath {path:?}: {source}")]
+    InvalidPath {
+        path: String,
+        source: serde_json_path::ParseError,
+    },
+}
+
+/// Selects a part of a JSON document to disclose.
+///
+/// In JSON a selector reads as `{"pointer": "/a/0"}` or `{"path": "$.a[*].b"}`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonSelector {
+    /// A JSON pointer (RFC 6901).
+    Pointer(String),
+    /// A JSONPath query (RFC 9535), which may select several values.
+    Path(String),
+}
+
+/// Returns the ranges of the received body which are not selected by `selectors`.
+///
+/// A selected object member is disclosed together with its key, and a selected number or literal
+/// with the byte after it. So that a verifier can locate the selected values, the path to them
+/// also stays disclosed: the brackets of the enclosing objects and arrays, the keys leading to the
+/// values and the separators of the array items before them. Everything else in the body is
+/// returned, such as the other members and items. The framing of a chunked body is not returned,
+/// so that the body can still be decoded from the disclosed transcript.
+///
+/// A key repeated in an object selects its last member, as with `serde_json`.
+pub(crate) fn undisclosed_body_ranges(
+    selectors: &[JsonSelector],
+    recv: &[u8],
+) -> Result<RangeSet, DisclosureError> {
+    let message = HttpMessage::parse_response(recv)?;
+    let body = message.decoded_body(recv);
+    let document = JsonValue::parse(&body)?;
+
+    let mut pointers = Vec::new();
+    for selector in selectors {
+        match selector {
+            JsonSelector::Pointer(pointer) => pointers.push(pointer.clone()),
+            JsonSelector::Path(path) => {
+                let query =
+                    JsonPath::parse(path).map_err(|source| DisclosureError::InvalidPath {
+                        path: path.clone(),
+                        source,
+                    })?;
+                let value: serde_json::Value =
+                    serde_json::from_slice(&body).map_err(JsonError::from)?;
+                pointers.extend(
+                    query
+                        .query_located(&value)
+                        .locations()
+                        .map(|location| location.to_json_pointer()),
+                );
+            }
+        }
+    }
+
+    let mut disclosed = RangeSet::new();
+    for pointer in &pointers {
+        if let Some(ranges) = document.disclosure_ranges(pointer)? {
+            disclosed.extend(
+                ranges
+                    .into_iter()
+                    .map(|range| range.start..range.end.min(body.len())),
+            );
+        }
+    }
+
+    let mut undisclosed = RangeSet::new();
+    for range in disclosed.complement(0..body.len()).iter() {
+        undisclosed.extend(message.transcript_ranges(range.clone()));
+    }
+    Ok(undisclosed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn pointer(pointer: &str) -> JsonSelector {
+        JsonSelector::Pointer(pointer.to_string())
+    }
+
+    fn path(path: &str) -> JsonSelector {
+        JsonSelector::Path(path.to_string())
+    }
+
+    /// Returns what follows the head of `recv` once disclosed, with undisclosed bytes as `*`.
+    fn disclosed(selectors: &[JsonSelector], recv: &str) -> String {
+        let undisclosed = undisclosed_body_ranges(selectors, recv.as_bytes()).unwrap();
+        let head_len = recv.find("\r\n\r\n").unwrap() + 4;
+        let mut bytes = recv.as_bytes().to_vec();
+        for range in &undisclosed {
+            assert!(range.start >= head_len, "the head must stay disclosed");
+            bytes[range.clone()].fill(b'*');
+        }
+        String::from_utf8(bytes[head_len..].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn pointer_discloses_the_member_with_its_key() {
+        let recv = response(r#"{"id": 7, "balance": 337, "owner": "alice"}"#);
+        // The number is disclosed with the comma after it, which shows where it ends
+        assert_eq!(
+            disclosed(&[pointer("/balance")], &recv),
+            r#"{*********"balance": 337,*****************}"#
+        );
+    }
+
+    #[test]
+    fn punctuation_between_selected_members_stays_redacted() {
+        let recv = response(r#"{"a":"1","b":"2","c":"3"}"#);
+        assert_eq!(
+            disclosed(&[pointer("/a"), pointer("/b")], &recv),
+            r#"{"a":"1"*"b":"2"********}"#
+        );
+    }
+
+    #[test]
+    fn path_discloses_every_match() {
+        let recv = response(r#"{"items":[{"id":1,"secret":"x"},{"id":2,"secret":"y"}]}"#);
+        assert_eq!(
+            disclosed(&[path("$.items[*].id")], &recv),
+            r#"{"items":[{"id":1,************},{"id":2,************}]}"#
+        );
+    }
+
+    #[test]
+    fn array_items_are_located_by_their_separators() {
+        let recv = response(r#"{"m":[[1,2],[3,[4,5]]]}"#);
+        assert_eq!(
+            disclosed(&[pointer("/m/1/1/0")], &recv),
+            r#"{"m":[*****,[*,[4,*]]]}"#
+        );
+        assert_eq!(
+            disclosed(&[path("$.m[0]")], &recv),
+            r#"{"m":[[1,2]**********]}"#
+        );
+    }
+
+    #[test]
+    fn duplicate_keys_disclose_the_last_member() {
+        // As with serde_json, the last member wins, for pointers and JSONPath alike
+        let recv = response(r#"{"a":1,"a":2}"#);
+        assert_eq!(disclosed(&[pointer("/a")], &recv), r#"{******"a":2}"#);
+        assert_eq!(disclosed(&[path("$.a")], &recv), r#"{******"a":2}"#);
+    }
+
+    #[test]
+    fn unmatched_selectors_disclose_nothing() {
+        let recv = response(r#"{"a":1}"#);
+        assert_eq!(disclosed(&[pointer("/b"), path("$.c")], &recv), "*******");
+    }
+
+    #[test]
+    fn chunk_framing_stays_disclosed() {
+        let recv = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                    6\r\n{\"a\":1\r\n6\r\n,\"b\":2\r\n1\r\n}\r\n0\r\n\r\n";
+        assert_eq!(
+            disclosed(&[pointer("/b")], recv),
+            "6\r\n{*****\r\n6\r\n*\"b\":2\r\n1\r\n}\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn invalid_bodies_and_selectors_are_refused() {
+        let recv = response(r#"{"a":1}"#);
+        assert!(matches!(
+            undisclosed_body_ranges(&[path("$[")], recv.as_bytes()),
+            Err(DisclosureError::InvalidPath { .. })
+        ));
+        assert!(matches!(
+            undisclosed_body_ranges(&[pointer("a")], recv.as_bytes()),
+            Err(DisclosureError::Json(JsonError::InvalidPointer(_)))
+        ));
+        assert!(matches!(
+            undisclosed_body_ranges(&[pointer("/a")], response("not json").as_bytes()),
+            Err(DisclosureError::Json(JsonError::Invalid(_)))
+        ));
+        assert!(matches!(
+            undisclosed_body_ranges(&[pointer("/a")], b"not http"),
+            Err(DisclosureError::Http(_))
+        ));
+    }
+}
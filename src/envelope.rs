@@ -0,0 +1,461 @@
+use crate::{
+    disclose::JsonSelector,
+    notary_key::key_fingerprint,
+    output::{read_input, ProofFormat, ProofIoError},
+    redact::{Direction, HttpPart, Redaction, RedactionKind, RedactionMatch},
+    NotarizationRequest,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{ops::Range, path::Path};
+use tlsn_core::proof::TlsProof;
+
+/// Version of the envelope format written by this library.
+///
+/// The minor version is bumped for backward compatible additions; readers refuse envelopes
+/// with a different major version.
+pub const ENVELOPE_VERSION: &str = "1.0";
+const ENVELOPE_MAJOR_VERSION: u64 = 1;
+
+/// Replaces secrets in the request recorded in an envelope.
+const REDACTED_PLACEHOLDER: &str = "[redacted]";
+/// Headers which are never recorded in an envelope, whatever the redaction rules.
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+];
+
+/// Errors raised while reading a [`ProofEnvelope`].
+#[derive(Debug, thiserror::Error)]
+pub enum EnvelopeError {
+    #[error(transparent)]
+    Io(#[from] ProofIoError),
+    #[error("expected a proof envelope, but the data has no envelope version; proofs written without an envelope cannot be read")]
+    NotAnEnvelope,
+    #[error("invalid envelope version {0:?}")]
+    InvalidVersion(String),
+    #[error("unsupported envelope version {version}, this library reads version {ENVELOPE_MAJOR_VERSION}.x")]
+    UnsupportedVersion { version: String },
+}
+
+/// A [`TlsProof`] together with what it was made for.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProofEnvelope {
+    /// Version of the envelope format, see [`ENVELOPE_VERSION`].
+    pub version: String,
+    pub metadata: ProofMetadata,
+    pub proof: TlsProof,
+}
+
+/// Context of a proof, as recorded by the prover.
+///
+/// None of it is covered by the notary signature: it describes the proof, and must be checked
+/// against the verified presentation before being relied upon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofMetadata {
+    /// Address of the notary which ran the session, as `host:port`, if the prover recorded it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notary: Option<String>,
+    /// Fingerprint of the notary key which signed the session, if the prover knew it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notary_key_fingerprint: Option<String>,
+    /// The notarized request, with secrets replaced and without its redactions and disclosed
+    /// fields, which are recorded below.
+    pub request: NotarizationRequest,
+    /// Redaction rules applied to the transcripts, without the values they match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<RecordedRedaction>,
+    /// Selectors of the disclosed received body fields.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disclose: Vec<JsonSelector>,
+    /// Version of the library which made the proof.
+    pub library_version: String,
+    /// Time at which the envelope was made.
+    pub created_at: DateTime<Utc>,
+}
+
+impl ProofMetadata {
+    fn new(
+        request: &NotarizationRequest,
+        redactions: Vec<RecordedRedaction>,
+        notary_key: Option<&p256::PublicKey>,
+    ) -> Self {
+        let mut recorded = without_secrets(request);
+        recorded.redactions.clear();
+        let disclose = std::mem::take(&mut recorded.disclose);
+
+        Self {
+            notary: None,
+            notary_key_fingerprint: notary_key.map(key_fingerprint),
+            request: recorded,
+            redactions,
+            disclose,
+            library_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// A redaction rule as recorded in an envelope.
+///
+/// Only the kind of the rule is kept: literals and regexes are often the very secrets the rule
+/// hides.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRedaction {
+    pub direction: Direction,
+    pub kind: RedactionKind,
+    /// Ranges of the transcript the rule hid.
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl RecordedRedaction {
+    /// Records `redaction`, which hid `ranges` of its transcript.
+    pub fn new(redaction: &Redaction, ranges: Vec<Range<usize>>) -> Self {
+        Self {
+            direction: redaction.direction,
+            kind: redaction.target.kind(),
+            ranges,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    version: Option<String>,
+}
+
+impl ProofEnvelope {
+    /// Wraps `proof`, made for `request` whose redaction rules hid `redactions`, and signed by
+    /// `notary_key` if known.
+    pub fn new(
+        proof: TlsProof,
+        request: &NotarizationRequest,
+        redactions: Vec<RecordedRedaction>,
+        notary_key: Option<&p256::PublicKey>,
+    ) -> Self {
+        Self {
+            version: ENVELOPE_VERSION.to_string(),
+            metadata: ProofMetadata::new(request, redactions, notary_key),
+            proof,
+        }
+    }
+
+    /// Decodes an envelope in any [`ProofFormat`], refusing unknown major versions before
+    /// looking at the rest of it.
+    pub fn decode(data: &[u8]) -> Result<Self, EnvelopeError> {
+        let VersionProbe { version } = ProofFormat::decode(data)?;
+        let version = version.ok_or(EnvelopeError::NotAnEnvelope)?;
+        let major = version
+            .split('.')
+            .next()
+            .and_then(|major| major.parse::<u64>().ok())
+            .ok_or_else(|| EnvelopeError::InvalidVersion(version.clone()))?;
+        if major != ENVELOPE_MAJOR_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion { version });
+        }
+        Ok(ProofFormat::decode(data)?)
+    }
+
+    /// Reads an envelope from `path`, or from stdin if `path` is `-`.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, EnvelopeError> {
+        Self::decode(&read_input(path.as_ref())?)
+    }
+}
+
+/// Returns a copy of `request` without the values its sent redaction rules hide, nor the values
+/// of [`SENSITIVE_HEADERS`].
+///
+/// Literal and regex rules are applied to the path and header values. The body is dropped if
+/// any rule may hide part of it.
+fn without_secrets(request: &NotarizationRequest) -> NotarizationRequest {
+    let mut request = request.clone();
+    let sent_rules: Vec<RedactionMatch> = request
+        .redactions
+        .iter()
+        .filter(|redaction| redaction.direction == Direction::Sent)
+        .map(|redaction| redaction.target.clone())
+        .collect();
+
+    let mut drop_body = false;
+    for rule in &sent_rules {
+        match rule {
+            RedactionMatch::Literal(literal) if !literal.is_empty() => {
+                request.path = request.path.replace(literal, REDACTED_PLACEHOLDER);
+                for (_, value) in &mut request.headers {
+                    *value = value.replace(literal, REDACTED_PLACEHOLDER);
+                }
+                drop_body = true;
+            }
+            RedactionMatch::Regex(pattern) => {
+                match regex::Regex::new(pattern) {
+                    Ok(regex) => {
+                        request.path = regex
+                            .replace_all(&request.path, REDACTED_PLACEHOLDER)
+                            .into_owned();
+                        for (_, value) in &mut request.headers {
+                            *value = regex.replace_all(value, REDACTED_PLACEHOLDER).into_owned();
+                        }
+                    }
+                    // An invalid rule failed the notarization, but keep nothing it may hide
+                    Err(_) => {
+                        request.path = REDACTED_PLACEHOLDER.to_string();
+                        for (_, value) in &mut request.headers {
+                            *value = REDACTED_PLACEHOLDER.to_string();
+                        }
+                    }
+                }
+                drop_body = true;
+            }
+            RedactionMatch::Header(name) => {
+                for (header_name, value) in &mut request.headers {
+                    if header_name.eq_ignore_ascii_case(name) {
+                        *value = REDACTED_PLACEHOLDER.to_string();
+                    }
+                }
+            }
+            RedactionMatch::Part(HttpPart::StartLine) => {
+                request.path = REDACTED_PLACEHOLDER.to_string();
+            }
+            RedactionMatch::Part(HttpPart::Headers) => {
+                for (_, value) in &mut request.headers {
+                    *value = REDACTED_PLACEHOLDER.to_string();
+                }
+            }
+            RedactionMatch::Literal(_)
+            | RedactionMatch::JsonPointer(_)
+            | RedactionMatch::Part(HttpPart::Body) => drop_body = true,
+        }
+    }
+
+    for (name, value) in &mut request.headers {
+        if SENSITIVE_HEADERS
+            .iter()
+            .any(|sensitive| name.eq_ignore_ascii_case(sensitive))
+        {
+            *value = REDACTED_PLACEHOLDER.to_string();
+        }
+    }
+    if drop_body {
+        request.body = None;
+    }
+
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(redactions: Vec<Redaction>) -> NotarizationRequest {
+        serde_json::from_value(serde_json::json!({
+            "host": "api.example.com",
+            "path": "/v1/me?token=abc123",
+            "headers": [["Host", "api.example.com"], ["X-Signature", "sig-456"]],
+            "body": {"json": {"password": "hunter2"}},
+            "redactions": redactions,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn metadata_does_not_record_the_values_redactions_match() {
+        let redactions = vec![
+            Redaction::new(
+                Direction::Sent,
+                RedactionMatch::Regex("token=[a-z0-9]+".into()),
+            ),
+            Redaction::new(Direction::Sent, RedactionMatch::Literal("hunter2".into())),
+            Redaction::new(
+                Direction::Sent,
+                RedactionMatch::Header("X-Signature".into()),
+            ),
+            Redaction::new(Direction::Recv, RedactionMatch::Literal("s3cret".into())),
+        ];
+        let recorded: Vec<_> = redactions
+            .iter()
+            .map(|redaction| RecordedRedaction::new(redaction, vec![10..16, 20..26]))
+            .collect();
+        let metadata = ProofMetadata::new(&request(redactions), recorded, None);
+
+        assert!(metadata.request.redactions.is_empty());
+        assert_eq!(
+            metadata.redactions[1],
+            RecordedRedaction {
+                direction: Direction::Sent,
+                kind: RedactionKind::Literal,
+                ranges: vec![10..16, 20..26],
+            }
+        );
+        for format in [ProofFormat::Json, ProofFormat::Cbor] {
+            let encoded = format.encode(&metadata).unwrap();
+            for secret in ["abc123", "[a-z0-9]", "hunter2", "sig-456", "s3cret"] {
+                assert!(
+                    !encoded
+                        .windows(secret.len())
+                        .any(|window| window == secret.as_bytes()),
+                    "{format} metadata holds {secret:?}"
+                );
+            }
+        }
+    }
+
+    fn sent(target: RedactionMatch) -> Redaction {
+        Redaction::new(Direction::Sent, target)
+    }
+
+    /// Returns the path, header values and body of the request as recorded with `redactions`.
+    fn recorded(redactions: Vec<Redaction>) -> (String, Vec<String>, bool) {
+        let recorded = without_secrets(&request(redactions));
+        let values = recorded
+            .headers
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        (recorded.path, values, recorded.body.is_some())
+    }
+
+    #[test]
+    fn without_secrets_applies_each_kind_of_rule() {
+        let hidden = REDACTED_PLACEHOLDER;
+        let cases = [
+            (
+                vec![],
+                ("/v1/me?token=abc123", ["api.example.com", "sig-456"], true),
+            ),
+            (
+                vec![sent(RedactionMatch::Literal("abc".into()))],
+                (
+                    "/v1/me?token=[redacted]123",
+                    ["api.example.com", "sig-456"],
+                    false,
+                ),
+            ),
+            (
+                vec![sent(RedactionMatch::Regex("[0-9]{3}".into()))],
+                (
+                    "/v1/me?token=abc[redacted]",
+                    ["api.example.com", "sig-[redacted]"],
+                    false,
+                ),
+            ),
+            (
+                vec![sent(RedactionMatch::Regex("(".into()))],
+                (hidden, [hidden, hidden], false),
+            ),
+            (
+                vec![sent(RedactionMatch::Header("x-signature".into()))],
+                ("/v1/me?token=abc123", ["api.example.com", hidden], true),
+            ),
+            (
+                vec![sent(RedactionMatch::JsonPointer("/password".into()))],
+                ("/v1/me?token=abc123", ["api.example.com", "sig-456"], false),
+            ),
+            (
+                vec![sent(RedactionMatch::Part(HttpPart::StartLine))],
+                (hidden, ["api.example.com", "sig-456"], true),
+            ),
+            (
+                vec![sent(RedactionMatch::Part(HttpPart::Headers))],
+                ("/v1/me?token=abc123", [hidden, hidden], true),
+            ),
+            (
+                vec![sent(RedactionMatch::Part(HttpPart::Body))],
+                ("/v1/me?token=abc123", ["api.example.com", "sig-456"], false),
+            ),
+            // The request holds none of the received transcript
+            (
+                vec![Redaction::new(
+                    Direction::Recv,
+                    RedactionMatch::Literal("abc".into()),
+                )],
+                ("/v1/me?token=abc123", ["api.example.com", "sig-456"], true),
+            ),
+        ];
+        for (redactions, (path, headers, body)) in cases {
+            assert_eq!(
+                recorded(redactions.clone()),
+                (path.to_string(), headers.map(String::from).to_vec(), body),
+                "{redactions:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn without_secrets_always_hides_sensitive_headers() {
+        let mut request = request(Vec::new());
+        request.headers.extend([
+            ("authorization".to_string(), "Bearer t".to_string()),
+            ("Cookie".to_string(), "session=s".to_string()),
+            ("X-API-Key".to_string(), "k".to_string()),
+        ]);
+        let values: Vec<_> = without_secrets(&request)
+            .headers
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(
+            values,
+            [
+                "api.example.com",
+                "sig-456",
+                REDACTED_PLACEHOLDER,
+                REDACTED_PLACEHOLDER,
+                REDACTED_PLACEHOLDER
+            ]
+        );
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let redactions = vec![sent(RedactionMatch::Header("X-Signature".into()))];
+        let recorded = vec![RecordedRedaction::new(&redactions[0], vec![40..47, 60..63])];
+        let key = p256::SecretKey::from_slice(&[1; 32]).unwrap().public_key();
+        let metadata = ProofMetadata::new(&request(redactions), recorded, Some(&key));
+        assert_eq!(metadata.notary_key_fingerprint, Some(key_fingerprint(&key)));
+
+        for format in [ProofFormat::PrettyJson, ProofFormat::Cbor] {
+            let encoded = format.encode(&metadata).unwrap();
+            assert_eq!(
+                ProofFormat::decode::<ProofMetadata>(&encoded).unwrap(),
+                metadata,
+                "{format}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_major_versions_are_refused_before_the_rest() {
+        // Neither envelope has metadata or a proof: the version alone must refuse them
+        for format in [ProofFormat::Json, ProofFormat::Cbor] {
+            let encoded = format
+                .encode(&serde_json::json!({"version": "2.0"}))
+                .unwrap();
+            let err = ProofEnvelope::decode(&encoded).unwrap_err();
+            assert!(
+                matches!(&err, EnvelopeError::UnsupportedVersion { version } if version == "2.0"),
+                "{err}"
+            );
+        }
+
+        let err = ProofEnvelope::decode(br#"{"version": "one"}"#).unwrap_err();
+        assert!(matches!(err, EnvelopeError::InvalidVersion(_)), "{err}");
+        let err = ProofEnvelope::decode(br#"{"version": "1.0", "proof": {}}"#).unwrap_err();
+        assert!(matches!(err, EnvelopeError::Io(_)), "{err}");
+    }
+
+    #[test]
+    fn bare_proofs_are_refused_as_such() {
+        // The shape of a `TlsProof` written without an envelope
+        let bare = serde_json::json!({"session": {}, "substrings": {}});
+        for format in [ProofFormat::Json, ProofFormat::Cbor] {
+            let err = ProofEnvelope::decode(&format.encode(&bare).unwrap()).unwrap_err();
+            assert!(matches!(err, EnvelopeError::NotAnEnvelope), "{err}");
+            assert!(err.to_string().starts_with("expected a proof envelope"));
+        }
+        let err = ProofEnvelope::decode(b"{").unwrap_err();
+        assert!(matches!(err, EnvelopeError::Io(_)), "{err}");
+    }
+}
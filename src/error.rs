@@ -0,0 +1,215 @@
+use crate::{
+    disclose::DisclosureError,
+    redact::{Direction, RedactionError},
+    retry::RetryClass,
+    trust::TrustStoreError,
+};
+use hyper::StatusCode;
+use notary_client::ClientError;
+use std::{io, time::Duration};
+use tlsn_prover::tls::ProverError;
+
+/// Errors raised while notarizing a request.
+#[derive(Debug, thiserror::Error)]
+pub enum OpacityError {
+    #[error("failed to load the notary CA: {0}")]
+    CaLoading(#[from] TrustStoreError),
+    #[error("failed to build the notary client: {0}")]
+    ClientBuild(String),
+    #[error("could not connect to notary {host}:{port}: {source}")]
+    ConnectionRefused {
+        host: String,
+        port: u16,
+        source: ClientError,
+    },
+    #[error("TLS handshake with notary {host}:{port} failed: {source}")]
+    Tls {
+        host: String,
+        port: u16,
+        source: ClientError,
+    },
+    #[error("notary {host}:{port} rejected the notarization request: {source}")]
+    NotaryRejected {
+        host: String,
+        port: u16,
+        source: ClientError,
+    },
+    #[error("notary {host}:{port} did not accept the request within {timeout:?}")]
+    Timeout {
+        host: String,
+        port: u16,
+        timeout: Duration,
+    },
+    #[error("every notary failed: {}", join_errors(.0))]
+    AllNotariesFailed(Vec<OpacityError>),
+    #[error("failed to load the server trust store: {0}")]
+    ServerTrustStore(TrustStoreError),
+    #[error(
+        "{direction} data needs at least {needed} bytes but the session allows {limit}; \
+         raise the request's data limit"
+    )]
+    DataLimitExceeded {
+        direction: Direction,
+        needed: usize,
+        limit: usize,
+    },
+    #[error("invalid prover config: {0}")]
+    ProverConfig(String),
+    #[error("could not connect to server {host}:{port}: {source}")]
+    ServerConnect {
+        host: String,
+        port: u16,
+        source: io::Error,
+    },
+    #[error("invalid HTTP request: {0}")]
+    InvalidRequest(#[from] hyper::http::Error),
+    #[error("HTTP error: {0}")]
+    Http(#[from] hyper::Error),
+    #[error("server responded with unexpected status {0}")]
+    UnexpectedStatus(StatusCode),
+    #[error("prover error: {0}")]
+    Prover(#[from] ProverError),
+    #[error("prover task failed: {0}")]
+    ProverTask(#[from] tokio::task::JoinError),
+    #[error("failed to apply redactions: {0}")]
+    Redaction(#[from] RedactionError),
+    #[error("failed to select the disclosed fields: {0}")]
+    Disclosure(#[from] DisclosureError),
+    #[error("failed to build the proof: {0}")]
+    Proof(String),
+}
+
+impl OpacityError {
+    /// Classifies an error returned by `NotaryClient::request_notarization`.
+    pub(crate) fn from_client_error(source: ClientError, host: &str, port: u16) -> Self {
+        let host = host.to_string();
+
+        match ClientFailure::of(&source) {
+            ClientFailure::Connection => OpacityError::ConnectionRefused { host, port, source },
+            ClientFailure::Tls => OpacityError::Tls { host, port, source },
+            ClientFailure::Rejected => OpacityError::NotaryRejected { host, port, source },
+        }
+    }
+
+    /// Whether retrying the same request may succeed, i.e. whether the error is of one of the
+    /// [`RetryClass::DEFAULT`] classes.
+    pub fn is_transient(&self) -> bool {
+        RetryClass::of(self).is_some_and(|class| RetryClass::DEFAULT.contains(&class))
+    }
+}
+
+fn join_errors(errors: &[OpacityError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Coarse classification of a `ClientError`.
+enum ClientFailure {
+    Connection,
+    Tls,
+    Rejected,
+}
+
+impl ClientFailure {
+    /// `ClientError` keeps its kind private, so it is recovered from the I/O and TLS errors found
+    /// in its source chain. Errors caused by neither come from the notary turning the request
+    /// down.
+    fn of(err: &ClientError) -> Self {
+        Self::of_chain(err)
+    }
+
+    fn of_chain(err: &(dyn std::error::Error + 'static)) -> Self {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if err.is::<rustls::Error>() {
+                return ClientFailure::Tls;
+            }
+            if let Some(io_err) = err.downcast_ref::<io::Error>() {
+                return match io_err.get_ref() {
+                    Some(inner) if inner.is::<rustls::Error>() => ClientFailure::Tls,
+                    _ => ClientFailure::Connection,
+                };
+            }
+            source = err.source();
+        }
+
+        ClientFailure::Rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{error::Error, fmt};
+
+    /// An error standing for `ClientError`, whose debug output mimics it.
+    #[derive(Debug)]
+    struct Wrapped {
+        kind: &'static str,
+        source: Option<Box<dyn Error + Send + Sync>>,
+    }
+
+    impl fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{} error", self.kind)
+        }
+    }
+
+    impl Error for Wrapped {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            self.source
+                .as_deref()
+                .map(|err| err as &(dyn Error + 'static))
+        }
+    }
+
+    fn wrap(kind: &'static str, source: impl Error + Send + Sync + 'static) -> Wrapped {
+        Wrapped {
+            kind,
+            source: Some(Box::new(source)),
+        }
+    }
+
+    fn classify(err: &Wrapped) -> &'static str {
+        match ClientFailure::of_chain(err) {
+            ClientFailure::Connection => "connection",
+            ClientFailure::Tls => "tls",
+            ClientFailure::Rejected => "rejected",
+        }
+    }
+
+    #[test]
+    fn io_errors_are_connection_failures() {
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(classify(&wrap("Internal", refused)), "connection");
+
+        // However deep in the chain
+        let timed_out = wrap("Connection", io::Error::from(io::ErrorKind::TimedOut));
+        assert_eq!(classify(&wrap("Internal", timed_out)), "connection");
+    }
+
+    #[test]
+    fn rustls_errors_are_tls_failures() {
+        let handshake = rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer);
+        assert_eq!(classify(&wrap("Internal", handshake.clone())), "tls");
+
+        // As returned by `tokio-rustls`, wrapped in an I/O error
+        let io_err = io::Error::new(io::ErrorKind::InvalidData, handshake);
+        assert_eq!(classify(&wrap("Connection", io_err)), "tls");
+    }
+
+    #[test]
+    fn other_errors_are_rejections() {
+        let no_source = Wrapped {
+            kind: "Connection",
+            source: None,
+        };
+        assert_eq!(classify(&no_source), "rejected");
+
+        let status = wrap("TlsSetup", fmt::Error);
+        assert_eq!(classify(&status), "rejected");
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{config::NotaryEndpoint, NotaryConfig, NotaryKeyError, OpacityError};
+use lazy_static::lazy_static;
+use rand::seq::SliceRandom;
+use reqwest::ClientBuilder;
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+use tracing::debug;
+
+lazy_static! {
+    /// When each notary last failed, by address.
+    static ref FAILURES: Mutex<HashMap<String, Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Returns the notaries of `config` in the order sessions should be requested from them.
+///
+/// Notaries which failed within the config's cooldown, or their health check when enabled, come
+/// last. The others are sorted by priority, in random order within a priority.
+pub(crate) async fn candidates(config: &NotaryConfig) -> Vec<NotaryEndpoint> {
+    let mut endpoints = config.endpoints();
+    if endpoints.len() == 1 {
+        return endpoints;
+    }
+    endpoints.shuffle(&mut rand::thread_rng());
+
+    let healthy = if config.health_check {
+        futures::future::join_all(
+            endpoints
+                .iter()
+                .map(|endpoint| is_healthy(config.for_endpoint(endpoint))),
+        )
+        .await
+    } else {
+        vec![true; endpoints.len()]
+    };
+
+    let mut candidates: Vec<(bool, NotaryEndpoint)> = endpoints
+        .into_iter()
+        .zip(healthy)
+        .map(|(endpoint, healthy)| {
+            let available = healthy && !failed_recently(config, &endpoint);
+            (!available, endpoint)
+        })
+        .collect();
+    // The sort is stable, keeping the shuffled order within a priority
+    candidates.sort_by_key(|(unavailable, endpoint)| (*unavailable, endpoint.priority));
+    candidates
+        .into_iter()
+        .map(|(_, endpoint)| endpoint)
+        .collect()
+}
+
+/// Whether another notary should be tried after `err`: the notary could not be reached, did not
+/// answer in time or turned the session down.
+pub(crate) fn fails_over(err: &OpacityError) -> bool {
+    matches!(
+        err,
+        OpacityError::ConnectionRefused { .. }
+            | OpacityError::Tls { .. }
+            | OpacityError::NotaryRejected { .. }
+            | OpacityError::Timeout { .. }
+    )
+}
+
+pub(crate) fn record_failure(endpoint: &NotaryEndpoint) {
+    FAILURES
+        .lock()
+        .unwrap()
+        .insert(endpoint.address(), Instant::now());
+}
+
+pub(crate) fn record_success(endpoint: &NotaryEndpoint) {
+    FAILURES.lock().unwrap().remove(&endpoint.address());
+}
+
+fn failed_recently(config: &NotaryConfig, endpoint: &NotaryEndpoint) -> bool {
+    FAILURES
+        .lock()
+        .unwrap()
+        .get(&endpoint.address())
+        .is_some_and(|failed_at| failed_at.elapsed() < config.unhealthy_cooldown)
+}
+
+/// Queries the `/healthcheck` endpoint of the notary of `config`.
+async fn is_healthy(config: NotaryConfig) -> bool {
+    match health_check(&config).await {
+        Ok(()) => true,
+        Err(err) => {
+            debug!(
+                "Health check of {}:{} failed: {err}",
+                config.host, config.port
+            );
+            false
+        }
+    }
+}
+
+async fn health_check(config: &NotaryConfig) -> Result<(), NotaryKeyError> {
+    let scheme = if config.tls { "https" } else { "http" };
+    let url = format!("{}://{}:{}/healthcheck", scheme, config.host, config.port);
+
+    let builder = ClientBuilder::new().timeout(config.connect_timeout);
+    let client = config.trust_store.configure_reqwest(builder)?.build()?;
+    client.get(url).send().await?.error_for_status()?;
+    Ok(())
+}
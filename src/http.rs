@@ -0,0 +1,673 @@
+//! Parses the sent and received transcripts as HTTP/1.1 messages, recording where each part of
+//! a message lives so that it can be redacted or disclosed on its own.
+
+use std::ops::Range;
+
+/// Errors raised while parsing a transcript as an HTTP/1.1 message.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum HttpParseError {
+    #[error("message head is not terminated by an empty line")]
+    UnterminatedHead,
+    #[error("malformed {0} line")]
+    MalformedStartLine(&'static str),
+    #[error("malformed header line at byte {0}")]
+    MalformedHeader(usize),
+    #[error("invalid Content-Length header")]
+    InvalidContentLength,
+    #[error("body ends before its declared length")]
+    TruncatedBody,
+    #[error("malformed chunk at byte {0}")]
+    MalformedChunk(usize),
+}
+
+/// First line of an HTTP message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartLine {
+    /// `GET /path HTTP/1.1`
+    Request {
+        method: Range<usize>,
+        target: Range<usize>,
+        version: Range<usize>,
+    },
+    /// `HTTP/1.1 200 OK`
+    Status {
+        version: Range<usize>,
+        code: Range<usize>,
+        reason: Range<usize>,
+    },
+}
+
+/// A header field of an HTTP message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpHeader {
+    /// The whole line, without the trailing CRLF.
+    pub line: Range<usize>,
+    pub name: Range<usize>,
+    /// The value, without surrounding whitespace.
+    pub value: Range<usize>,
+}
+
+/// Layout of an HTTP/1.1 message inside a transcript.
+///
+/// Every range indexes the transcript the message was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpMessage {
+    /// The request or status line, without the trailing CRLF.
+    pub start_line: Range<usize>,
+    pub start: StartLine,
+    pub headers: Vec<HttpHeader>,
+    /// The whole head, from the start line up to and including the empty line.
+    pub head: Range<usize>,
+    /// The body as sent on the wire, so chunked bodies include their chunk framing.
+    pub body: Range<usize>,
+    /// Where the bytes of the decoded body live, in order: the data of every chunk of a chunked
+    /// body, or the whole body otherwise.
+    pub body_chunks: Vec<Range<usize>>,
+}
+
+impl HttpMessage {
+    /// Parses the request at the start of a sent transcript.
+    pub fn parse_request(transcript: &[u8]) -> Result<Self, HttpParseError> {
+        Self::parse(transcript, true)
+    }
+
+    /// Parses the response at the start of a received transcript.
+    pub fn parse_response(transcript: &[u8]) -> Result<Self, HttpParseError> {
+        Self::parse(transcript, false)
+    }
+
+    /// Returns the headers called `name`, compared case-insensitively.
+    pub fn headers_named<'a>(
+        &'a self,
+        transcript: &'a [u8],
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a HttpHeader> + 'a {
+        self.headers.iter().filter(move |header| {
+            transcript[header.name.clone()].eq_ignore_ascii_case(name.as_bytes())
+        })
+    }
+
+    /// Whether the body is sent with the chunked transfer coding.
+    pub fn is_chunked(&self, transcript: &[u8]) -> bool {
+        self.headers_named(transcript, "transfer-encoding")
+            .last()
+            .and_then(|header| {
+                transcript[header.value.clone()]
+                    .rsplit(|&byte| byte == b',')
+                    .next()
+            })
+            .is_some_and(|coding| coding.trim_ascii().eq_ignore_ascii_case(b"chunked"))
+    }
+
+    /// Returns the body without its chunk framing.
+    pub fn decoded_body(&self, transcript: &[u8]) -> Vec<u8> {
+        self.body_chunks
+            .iter()
+            .flat_map(|chunk| &transcript[chunk.clone()])
+            .copied()
+            .collect()
+    }
+
+    /// Maps a range of the decoded body back to the ranges of the transcript holding its bytes,
+    /// one for every chunk it spans.
+    pub fn transcript_ranges(&self, decoded: Range<usize>) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        for chunk in &self.body_chunks {
+            let start = decoded.start.max(offset);
+            let end = decoded.end.min(offset + chunk.len());
+            if start < end {
+                ranges.push(chunk.start + (start - offset)..chunk.start + (end - offset));
+            }
+            offset += chunk.len();
+        }
+        ranges
+    }
+
+    fn parse(transcript: &[u8], is_request: bool) -> Result<Self, HttpParseError> {
+        let head_len = find(transcript, b"\r\n\r\n").ok_or(HttpParseError::UnterminatedHead)? + 4;
+
+        let mut lines = Lines {
+            transcript,
+            pos: 0,
+            end: head_len - 2,
+        };
+
+        let start_line = lines.next().ok_or(HttpParseError::UnterminatedHead)?;
+        let start = if is_request {
+            parse_request_line(transcript, start_line.clone())
+                .ok_or(HttpParseError::MalformedStartLine("request"))?
+        } else {
+            parse_status_line(transcript, start_line.clone())
+                .ok_or(HttpParseError::MalformedStartLine("status"))?
+        };
+
+        let headers = lines
+            .map(|line| parse_header(transcript, line))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut message = HttpMessage {
+            start_line,
+            start,
+            headers,
+            head: 0..head_len,
+            body: head_len..transcript.len(),
+            body_chunks: Vec::new(),
+        };
+
+        // The transfer coding takes precedence over any Content-Length (RFC 9112, 6.3)
+        if message.is_chunked(transcript) {
+            let (chunks, end) = parse_chunks(transcript, head_len)?;
+            message.body = head_len..end;
+            message.body_chunks = chunks;
+            return Ok(message);
+        }
+
+        let mut content_length = None;
+        for header in message.headers_named(transcript, "content-length") {
+            let value = std::str::from_utf8(&transcript[header.value.clone()])
+                .ok()
+                .and_then(|value| value.parse::<usize>().ok())
+                .ok_or(HttpParseError::InvalidContentLength)?;
+            // Repeated headers must agree, or the body could be framed in two ways
+            if content_length.is_some_and(|length| length != value) {
+                return Err(HttpParseError::InvalidContentLength);
+            }
+            content_length = Some(value);
+        }
+
+        if let Some(content_length) = content_length {
+            if head_len + content_length > transcript.len() {
+                return Err(HttpParseError::TruncatedBody);
+            }
+            message.body = head_len..head_len + content_length;
+        }
+        if !message.body.is_empty() {
+            message.body_chunks = vec![message.body.clone()];
+        }
+
+        Ok(message)
+    }
+}
+
+/// Iterates over the CRLF-terminated lines of a message head.
+struct Lines<'a> {
+    transcript: &'a [u8],
+    pos: usize,
+    end: usize,
+}
+
+impl Iterator for Lines<'_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        if self.pos >= self.end {
+            return None;
+        }
+        let len = find(&self.transcript[self.pos..self.end], b"\r\n")?;
+        let line = self.pos..self.pos + len;
+        self.pos += len + 2;
+        Some(line)
+    }
+}
+
+/// Parses the chunked body starting at `pos`, returning the data of every chunk and where the
+/// body ends, after the last chunk and any trailer fields.
+fn parse_chunks(
+    transcript: &[u8],
+    mut pos: usize,
+) -> Result<(Vec<Range<usize>>, usize), HttpParseError> {
+    let mut chunks = Vec::new();
+    loop {
+        let line_len = find(&transcript[pos..], b"\r\n").ok_or(HttpParseError::TruncatedBody)?;
+        // The size may be followed by chunk extensions, which are ignored
+        let size = transcript[pos..pos + line_len]
+            .split(|&byte| byte == b';')
+            .next()
+            .and_then(|size| std::str::from_utf8(size).ok())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .ok_or(HttpParseError::MalformedChunk(pos))?;
+        pos += line_len + 2;
+        if size == 0 {
+            break;
+        }
+
+        let end = pos
+            .checked_add(size)
+            .ok_or(HttpParseError::MalformedChunk(pos))?;
+        let next = end
+            .checked_add(2)
+            .ok_or(HttpParseError::MalformedChunk(end))?;
+        let Some(terminator) = transcript.get(end..next) else {
+            return Err(HttpParseError::TruncatedBody);
+        };
+        if terminator != b"\r\n" {
+            return Err(HttpParseError::MalformedChunk(end));
+        }
+        chunks.push(pos..end);
+        pos = next;
+    }
+
+    // Trailer fields, up to and including the empty line ending the body
+    loop {
+        let line_len = find(&transcript[pos..], b"\r\n").ok_or(HttpParseError::TruncatedBody)?;
+        pos += line_len + 2;
+        if line_len == 0 {
+            return Ok((chunks, pos));
+        }
+    }
+}
+
+/// Header fields of the header maps of `hyper` and of `reqwest`, which build on different
+/// versions of the `http` crate.
+pub(crate) trait HeaderFields {
+    /// Iterates over the names and values of the fields, in order.
+    fn fields(&self) -> impl Iterator<Item = (&str, &[u8])>;
+}
+
+impl HeaderFields for hyper::HeaderMap {
+    fn fields(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.iter()
+            .map(|(name, value)| (name.as_str(), value.as_bytes()))
+    }
+}
+
+impl HeaderFields for reqwest::header::HeaderMap {
+    fn fields(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.iter()
+            .map(|(name, value)| (name.as_str(), value.as_bytes()))
+    }
+}
+
+/// Number of bytes a message head takes on the wire, given its start line without the line
+/// break.
+///
+/// Header lines are counted as `name: value`, as written by `hyper`. Received heads may be
+/// spaced differently, so their length is a lower bound.
+pub(crate) fn head_len(start_line: &str, headers: &impl HeaderFields) -> usize {
+    let header_lines: usize = headers
+        .fields()
+        .map(|(name, value)| name.len() + ": \r\n".len() + value.len())
+        .sum();
+    start_line.len() + "\r\n".len() + header_lines + "\r\n".len()
+}
+
+/// Start line of a response with status `code`, and its canonical reason phrase if any.
+pub(crate) fn status_line(code: &str, reason: Option<&str>) -> String {
+    format!("HTTP/1.1 {code} {}", reason.unwrap_or_default())
+}
+
+/// Returns the value of the first `Content-Length` header, if it is a valid length.
+pub(crate) fn content_length(headers: &impl HeaderFields) -> Option<usize> {
+    let (_, value) = headers
+        .fields()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))?;
+    std::str::from_utf8(value).ok()?.trim().parse().ok()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Splits a start line into its three space separated parts.
+fn split_start_line(
+    transcript: &[u8],
+    line: Range<usize>,
+) -> Option<(Range<usize>, Range<usize>, Range<usize>)> {
+    let bytes = &transcript[line.clone()];
+    let first = bytes.iter().position(|&byte| byte == b' ')?;
+    let second = first + 1 + bytes[first + 1..].iter().position(|&byte| byte == b' ')?;
+    Some((
+        line.start..line.start + first,
+        line.start + first + 1..line.start + second,
+        line.start + second + 1..line.end,
+    ))
+}
+
+fn parse_request_line(transcript: &[u8], line: Range<usize>) -> Option<StartLine> {
+    let (method, target, version) = split_start_line(transcript, line)?;
+    if method.is_empty() || target.is_empty() || !transcript[version.clone()].starts_with(b"HTTP/")
+    {
+        return None;
+    }
+    Some(StartLine::Request {
+        method,
+        target,
+        version,
+    })
+}
+
+fn parse_status_line(transcript: &[u8], line: Range<usize>) -> Option<StartLine> {
+    // The reason phrase may be empty, in which case the second space is optional.
+    let (version, code, reason) = split_start_line(transcript, line.clone()).or_else(|| {
+        let space = transcript[line.clone()]
+            .iter()
+            .position(|&byte| byte == b' ')?;
+        Some((
+            line.start..line.start + space,
+            line.start + space + 1..line.end,
+            line.end..line.end,
+        ))
+    })?;
+    if !transcript[version.clone()].starts_with(b"HTTP/")
+        || code.len() != 3
+        || !transcript[code.clone()].iter().all(u8::is_ascii_digit)
+    {
+        return None;
+    }
+    Some(StartLine::Status {
+        version,
+        code,
+        reason,
+    })
+}
+
+fn parse_header(transcript: &[u8], line: Range<usize>) -> Result<HttpHeader, HttpParseError> {
+    let colon = transcript[line.clone()]
+        .iter()
+        .position(|&byte| byte == b':')
+        .filter(|&colon| colon > 0)
+        .ok_or(HttpParseError::MalformedHeader(line.start))?;
+
+    let name = line.start..line.start + colon;
+    let mut value = line.start + colon + 1..line.end;
+    while value.start < value.end && matches!(transcript[value.start], b' ' | b'\t') {
+        value.start += 1;
+    }
+    while value.end > value.start && matches!(transcript[value.end - 1], b' ' | b'\t') {
+        value.end -= 1;
+    }
+
+    Ok(HttpHeader { line, name, value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text<'a>(transcript: &'a [u8], range: &Range<usize>) -> &'a str {
+        std::str::from_utf8(&transcript[range.clone()]).unwrap()
+    }
+
+    fn status(transcript: &[u8]) -> (&str, &str, &str) {
+        let message = HttpMessage::parse_response(transcript).unwrap();
+        let StartLine::Status {
+            version,
+            code,
+            reason,
+        } = message.start
+        else {
+            panic!("expected a status line");
+        };
+        (
+            text(transcript, &version),
+            text(transcript, &code),
+            text(transcript, &reason),
+        )
+    }
+
+    #[test]
+    fn request_line_is_split() {
+        let request = b"POST /a?b=c HTTP/1.1\r\nHost: x\r\n\r\n";
+        let message = HttpMessage::parse_request(request).unwrap();
+        let StartLine::Request {
+            method,
+            target,
+            version,
+        } = message.start
+        else {
+            panic!("expected a request line");
+        };
+        assert_eq!(text(request, &method), "POST");
+        assert_eq!(text(request, &target), "/a?b=c");
+        assert_eq!(text(request, &version), "HTTP/1.1");
+        assert_eq!(text(request, &message.start_line), "POST /a?b=c HTTP/1.1");
+        assert!(message.body.is_empty());
+        assert!(message.body_chunks.is_empty());
+    }
+
+    #[test]
+    fn reason_phrase_may_be_missing() {
+        assert_eq!(status(b"HTTP/1.1 204\r\n\r\n"), ("HTTP/1.1", "204", ""));
+        assert_eq!(status(b"HTTP/1.1 204 \r\n\r\n"), ("HTTP/1.1", "204", ""));
+        assert_eq!(
+            status(b"HTTP/1.1 404 Not Found\r\n\r\n"),
+            ("HTTP/1.1", "404", "Not Found")
+        );
+    }
+
+    #[test]
+    fn malformed_start_lines_are_refused() {
+        for response in [
+            &b"HTTP/1.1 20 OK\r\n\r\n"[..],
+            b"HTTP/1.1\r\n\r\n",
+            b"200 OK\r\n\r\n",
+        ] {
+            assert_eq!(
+                HttpMessage::parse_response(response),
+                Err(HttpParseError::MalformedStartLine("status"))
+            );
+        }
+        assert_eq!(
+            HttpMessage::parse_request(b"GET /\r\n\r\n"),
+            Err(HttpParseError::MalformedStartLine("request"))
+        );
+        assert_eq!(
+            HttpMessage::parse_request(b"GET / HTTP/1.1\r\n"),
+            Err(HttpParseError::UnterminatedHead)
+        );
+    }
+
+    #[test]
+    fn whitespace_around_values_is_trimmed() {
+        let response = b"HTTP/1.1 200 OK\r\nX-A:value\r\nX-B: \t spaced value \t\r\nX-C:\r\n\r\n";
+        let message = HttpMessage::parse_response(response).unwrap();
+        let values: Vec<_> = message
+            .headers
+            .iter()
+            .map(|header| (text(response, &header.name), text(response, &header.value)))
+            .collect();
+        assert_eq!(
+            values,
+            [("X-A", "value"), ("X-B", "spaced value"), ("X-C", "")]
+        );
+        assert_eq!(
+            text(response, &message.headers[1].line),
+            "X-B: \t spaced value \t"
+        );
+    }
+
+    #[test]
+    fn header_without_name_is_refused() {
+        assert_eq!(
+            HttpMessage::parse_response(b"HTTP/1.1 200 OK\r\n: value\r\n\r\n"),
+            Err(HttpParseError::MalformedHeader(17))
+        );
+        assert_eq!(
+            HttpMessage::parse_response(b"HTTP/1.1 200 OK\r\nno colon\r\n\r\n"),
+            Err(HttpParseError::MalformedHeader(17))
+        );
+    }
+
+    #[test]
+    fn duplicate_headers_are_found_whatever_their_case() {
+        let response =
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nX-Other: 0\r\nset-cookie: b=2\r\nSET-COOKIE: c=3\r\n\r\n";
+        let message = HttpMessage::parse_response(response).unwrap();
+        let cookies: Vec<_> = message
+            .headers_named(response, "Set-Cookie")
+            .map(|header| text(response, &header.value))
+            .collect();
+        assert_eq!(cookies, ["a=1", "b=2", "c=3"]);
+    }
+
+    #[test]
+    fn body_is_framed_by_content_length() {
+        let response = b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhelloHTTP/1.1 200 OK";
+        let message = HttpMessage::parse_response(response).unwrap();
+        assert_eq!(text(response, &message.body), "hello");
+        assert_eq!(message.decoded_body(response), b"hello");
+        assert_eq!(message.body_chunks.len(), 1);
+        assert_eq!(text(response, &message.body_chunks[0]), "hello");
+    }
+
+    #[test]
+    fn body_shorter_than_its_content_length_is_refused() {
+        assert_eq!(
+            HttpMessage::parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nhello"),
+            Err(HttpParseError::TruncatedBody)
+        );
+    }
+
+    #[test]
+    fn invalid_or_conflicting_content_lengths_are_refused() {
+        for response in [
+            &b"HTTP/1.1 200 OK\r\nContent-Length: five\r\n\r\nhello"[..],
+            b"HTTP/1.1 200 OK\r\nContent-Length: -5\r\n\r\nhello",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 4\r\n\r\nhello",
+        ] {
+            assert_eq!(
+                HttpMessage::parse_response(response),
+                Err(HttpParseError::InvalidContentLength)
+            );
+        }
+
+        let repeated = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\ncontent-length:  5 \r\n\r\nhello";
+        let message = HttpMessage::parse_response(repeated).unwrap();
+        assert_eq!(text(repeated, &message.body), "hello");
+    }
+
+    #[test]
+    fn body_without_length_runs_to_the_end() {
+        let response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nhello";
+        let message = HttpMessage::parse_response(response).unwrap();
+        assert_eq!(text(response, &message.body), "hello");
+    }
+
+    #[test]
+    fn head_ends_at_the_first_empty_line() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 15\r\n\r\nfirst\r\n\r\nsecond";
+        let message = HttpMessage::parse_response(response).unwrap();
+        assert_eq!(message.headers.len(), 1);
+        assert_eq!(text(response, &message.head).len(), 39);
+        assert_eq!(text(response, &message.body), "first\r\n\r\nsecond");
+    }
+
+    #[test]
+    fn chunked_body_is_decoded() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, Chunked\r\n\r\n\
+                         5;ext=1\r\nhello\r\nB\r\n, \r\n\r\nworld\r\n0\r\nX-Trailer: 1\r\n\r\nnext";
+        let message = HttpMessage::parse_response(response).unwrap();
+
+        assert!(message.is_chunked(response));
+        assert_eq!(message.decoded_body(response), b"hello, \r\n\r\nworld");
+        assert!(text(response, &message.body).starts_with("5;ext=1\r\n"));
+        assert!(text(response, &message.body).ends_with("X-Trailer: 1\r\n\r\n"));
+        let chunks: Vec<_> = message
+            .body_chunks
+            .iter()
+            .map(|chunk| text(response, chunk))
+            .collect();
+        assert_eq!(chunks, ["hello", ", \r\n\r\nworld"]);
+    }
+
+    #[test]
+    fn chunked_body_takes_precedence_over_content_length() {
+        let response =
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nTransfer-Encoding: chunked\r\n\r\n\
+                         3\r\nabc\r\n0\r\n\r\n";
+        let message = HttpMessage::parse_response(response).unwrap();
+        assert_eq!(message.decoded_body(response), b"abc");
+        assert_eq!(message.body.end, response.len());
+    }
+
+    #[test]
+    fn decoded_ranges_map_back_to_the_transcript() {
+        let response =
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n4\r\ndefg\r\n0\r\n\r\n";
+        let message = HttpMessage::parse_response(response).unwrap();
+        let mapped = |decoded: Range<usize>| -> Vec<&str> {
+            message
+                .transcript_ranges(decoded)
+                .iter()
+                .map(|range| text(response, range))
+                .collect()
+        };
+
+        assert_eq!(mapped(0..3), ["abc"]);
+        assert_eq!(mapped(1..5), ["bc", "de"]);
+        assert_eq!(mapped(3..7), ["defg"]);
+        assert_eq!(mapped(0..7), ["abc", "defg"]);
+        assert!(mapped(7..9).is_empty());
+        assert!(mapped(2..2).is_empty());
+    }
+
+    #[test]
+    fn malformed_or_truncated_chunks_are_refused() {
+        let head = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let parse = |body: &str| HttpMessage::parse_response(format!("{head}{body}").as_bytes());
+
+        assert_eq!(
+            parse("x\r\nabc\r\n0\r\n\r\n"),
+            Err(HttpParseError::MalformedChunk(47))
+        );
+        assert_eq!(
+            parse("3\r\nabcd\r\n0\r\n\r\n"),
+            Err(HttpParseError::MalformedChunk(53))
+        );
+        assert_eq!(parse("3\r\nab"), Err(HttpParseError::TruncatedBody));
+        assert_eq!(parse("3\r\nabc\r\n"), Err(HttpParseError::TruncatedBody));
+        assert_eq!(
+            parse("3\r\nabc\r\n0\r\n"),
+            Err(HttpParseError::TruncatedBody)
+        );
+
+        // Sizes whose end, or the end of its terminator, overflows
+        assert_eq!(
+            parse("ffffffffffffffff\r\nabc\r\n0\r\n\r\n"),
+            Err(HttpParseError::MalformedChunk(65))
+        );
+        let size = format!("{:016x}", usize::MAX - 65);
+        assert_eq!(
+            parse(&format!("{size}\r\nabc\r\n0\r\n\r\n")),
+            Err(HttpParseError::MalformedChunk(usize::MAX))
+        );
+    }
+
+    #[test]
+    fn head_len_counts_the_head_as_written() {
+        let fields = [
+            ("content-type", "application/json"),
+            ("content-length", " 42 "),
+        ];
+        let head = "HTTP/1.1 200 OK\r\n\
+                    content-type: application/json\r\n\
+                    content-length:  42 \r\n\
+                    \r\n";
+
+        let hyper_headers: hyper::HeaderMap = fields
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect();
+        let reqwest_headers: reqwest::header::HeaderMap = fields
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), value.parse().unwrap()))
+            .collect();
+        let status_line = status_line("200", Some("OK"));
+        assert_eq!(head_len(&status_line, &hyper_headers), head.len());
+        assert_eq!(head_len(&status_line, &reqwest_headers), head.len());
+        assert_eq!(content_length(&hyper_headers), Some(42));
+        assert_eq!(content_length(&reqwest_headers), Some(42));
+    }
+
+    #[test]
+    fn missing_or_invalid_content_length_is_none() {
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(content_length(&headers), None);
+        headers.insert("content-length", "-1".parse().unwrap());
+        assert_eq!(content_length(&headers), None);
+        assert_eq!(status_line("299", None), "HTTP/1.1 299 ");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{NotaryConfig, NotaryKeyError};
+use elliptic_curve::pkcs8::DecodePublicKey;
+use lazy_static::lazy_static;
+use reqwest::ClientBuilder;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Version of tlsn this library was written against, as reported by a notary built from it.
+///
+/// The tlsn dependencies are not pinned to a revision, so this is the version expected of
+/// notaries rather than the one of the crates in the build.
+pub const TLSN_VERSION: &str = "0.1.0-alpha.5";
+/// How long [`InfoClient::shared`] keeps the `/info` of a notary.
+pub const DEFAULT_INFO_TTL: Duration = Duration::from_secs(300);
+
+lazy_static! {
+    static ref SHARED_INFO_CLIENT: InfoClient = InfoClient::new(DEFAULT_INFO_TTL);
+}
+
+/// Response object of the /info API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InfoResponse {
+    /// Current version of notary-server
+    pub version: String,
+    /// Public key of the notary signing key
+    pub public_key: String,
+    /// Current git commit hash of notary-server
+    pub git_commit_hash: String,
+    /// Current git commit timestamp of notary-server
+    pub git_commit_timestamp: String,
+}
+
+impl InfoResponse {
+    /// Parses the PEM encoded signing key of the notary.
+    pub fn signing_key(&self) -> Result<p256::PublicKey, NotaryKeyError> {
+        p256::PublicKey::from_public_key_pem(self.public_key.trim())
+            .map_err(|err| NotaryKeyError::InvalidKey(err.to_string()))
+    }
+
+    /// Returns how the notary's version differs from [`TLSN_VERSION`], if it does.
+    ///
+    /// The notarization protocol changes between pre-releases, so any difference may break
+    /// sessions with the notary.
+    pub fn version_mismatch(&self) -> Option<VersionMismatch> {
+        let notary = self.version.trim().trim_start_matches('v');
+        // Build metadata does not affect the protocol
+        let notary = notary.split('+').next().unwrap_or_default();
+        (notary != TLSN_VERSION).then(|| VersionMismatch {
+            notary: self.version.clone(),
+            expected: TLSN_VERSION,
+        })
+    }
+}
+
+/// A notary running another version of tlsn than this library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMismatch {
+    /// Version reported by the notary.
+    pub notary: String,
+    /// Version this library is built against.
+    pub expected: &'static str,
+}
+
+impl fmt::Display for VersionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "notary runs tlsn {} but this library is built against {}, sessions may fail",
+            self.notary, self.expected
+        )
+    }
+}
+
+/// Fetches the `/info` of notaries, keeping each response for a while.
+///
+/// Fresh responses are checked against [`TLSN_VERSION`], and a warning is logged for notaries
+/// running another version.
+#[derive(Debug)]
+pub struct InfoClient {
+    ttl: Duration,
+    cache: Mutex<HashMap<String, (Instant, InfoResponse)>>,
+}
+
+impl InfoClient {
+    /// Creates a client keeping responses for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the client shared by the library, keeping responses for [`DEFAULT_INFO_TTL`].
+    pub fn shared() -> &'static Self {
+        &SHARED_INFO_CLIENT
+    }
+
+    /// Returns the `/info` of the configured notary, from the cache if it is fresh enough.
+    pub async fn info(&self, config: &NotaryConfig) -> Result<InfoResponse, NotaryKeyError> {
+        let url = info_url(config);
+        if let Some((fetched_at, info)) = self.cache.lock().unwrap().get(&url) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(info.clone());
+            }
+        }
+
+        let info = fetch_info(config).await?;
+        if let Some(mismatch) = info.version_mismatch() {
+            warn!("{}:{}: {mismatch}", config.host, config.port);
+        }
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(url, (Instant::now(), info.clone()));
+        Ok(info)
+    }
+
+    /// Forgets the `/info` of the configured notary, so that the next call fetches it again.
+    pub fn invalidate(&self, config: &NotaryConfig) {
+        self.cache.lock().unwrap().remove(&info_url(config));
+    }
+}
+
+/// Fetches the `/info` of the configured notary, bypassing any cache.
+///
+/// The response carries the notary's signing key, so it is only fetched over TLS, and the
+/// notary's certificate is checked against the config's trust store.
+pub async fn fetch_info(config: &NotaryConfig) -> Result<InfoResponse, NotaryKeyError> {
+    NotaryKeyError::require_tls(config)?;
+    let builder = ClientBuilder::new().timeout(config.request_timeout);
+    let client = config.trust_store.configure_reqwest(builder)?.build()?;
+
+    let info = client
+        .get(info_url(config))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(info)
+}
+
+fn info_url(config: &NotaryConfig) -> String {
+    format!("https://{}:{}/info", config.host, config.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(version: &str) -> InfoResponse {
+        InfoResponse {
+            version: version.to_string(),
+            public_key: String::new(),
+            git_commit_hash: String::new(),
+            git_commit_timestamp: String::new(),
+        }
+    }
+
+    #[test]
+    fn version_mismatch_ignores_prefix_and_build_metadata() {
+        for version in [
+            TLSN_VERSION.to_string(),
+            format!("v{TLSN_VERSION}"),
+            format!(" {TLSN_VERSION}+3f2a9c1\n"),
+        ] {
+            assert_eq!(info(&version).version_mismatch(), None, "{version:?}");
+        }
+    }
+
+    #[test]
+    fn other_versions_mismatch() {
+        for version in ["0.0.0", "", &format!("{TLSN_VERSION}.1")] {
+            assert_eq!(
+                info(version).version_mismatch(),
+                Some(VersionMismatch {
+                    notary: version.to_string(),
+                    expected: TLSN_VERSION,
+                }),
+                "{version:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Locates the byte ranges of values inside a JSON document.
+//!
+//! `serde_json` does not expose source positions, so documents are first validated with it and
+//! then walked by a small scanner which records where every value starts and ends.
+
+use std::ops::Range;
+
+/// Errors raised while locating values in a JSON document.
+#[derive(Debug, thiserror::Error)]
+pub enum JsonError {
+    #[error("invalid JSON: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("invalid JSON pointer {0:?}")]
+    InvalidPointer(String),
+}
+
+/// A JSON value together with its position in the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JsonValue {
+    /// Bytes of the value, including quotes and brackets.
+    pub range: Range<usize>,
+    pub node: JsonNode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum JsonNode {
+    Object(Vec<JsonMember>),
+    Array(Vec<JsonValue>),
+    String,
+    /// A number, `true`, `false` or `null`.
+    Scalar,
+}
+
+/// A member of a JSON object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JsonMember {
+    /// The unescaped key.
+    pub key: String,
+    /// Bytes of the key, including quotes.
+    pub key_range: Range<usize>,
+    pub value: JsonValue,
+}
+
+impl JsonValue {
+    /// Parses `input`, which must be a single JSON document.
+    ///
+    /// Ranges are relative to the start of `input`.
+    pub fn parse(input: &[u8]) -> Result<Self, JsonError> {
+        serde_json::from_slice::<serde::de::IgnoredAny>(input)?;
+
+        let mut scanner = Scanner { input, pos: 0 };
+        scanner.skip_whitespace();
+        Ok(scanner.value())
+    }
+
+    /// Resolves a JSON pointer (RFC 6901) against this value.
+    ///
+    /// Returns `Ok(None)` if the pointer is well formed but does not match anything. A key
+    /// repeated in an object resolves to its last member, as with `serde_json`.
+    pub fn pointer(&self, pointer: &str) -> Result<Option<&JsonValue>, JsonError> {
+        Ok(self.resolve(pointer)?.map(|resolved| resolved.value))
+    }
+
+    /// Like [`JsonValue::pointer`], but returns the ranges which disclose the matched value on
+    /// its own while still letting a reader locate it.
+    ///
+    /// These are the value, with its key when it is an object member, and the path to it from
+    /// the root: the brackets of the enclosing objects and arrays, the keys leading to the value
+    /// and the separators of the array items before it. A number or literal also takes the byte
+    /// after it, which shows where it ends; that byte may lie past the end of the document.
+    pub fn disclosure_ranges(&self, pointer: &str) -> Result<Option<Vec<Range<usize>>>, JsonError> {
+        let Some(Resolved {
+            value,
+            key_range,
+            mut path,
+        }) = self.resolve(pointer)?
+        else {
+            return Ok(None);
+        };
+
+        let start = key_range.map_or(value.range.start, |key_range| key_range.start);
+        let end = match value.node {
+            JsonNode::Scalar => value.range.end + 1,
+            _ => value.range.end,
+        };
+        path.push(start..end);
+        Ok(Some(path))
+    }
+
+    fn resolve(&self, pointer: &str) -> Result<Option<Resolved<'_>>, JsonError> {
+        let mut resolved = Resolved {
+            value: self,
+            key_range: None,
+            path: Vec::new(),
+        };
+        if pointer.is_empty() {
+            return Ok(Some(resolved));
+        }
+        let Some(tokens) = pointer.strip_prefix('/') else {
+            return Err(JsonError::InvalidPointer(pointer.to_string()));
+        };
+
+        for token in tokens.split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            let parent = resolved.value;
+            let next = match &parent.node {
+                JsonNode::Object(members) => members
+                    .iter()
+                    .rev()
+                    .find(|member| member.key == token)
+                    .map(|member| {
+                        resolved
+                            .path
+                            .push(member.key_range.start..member.value.range.start);
+                        (Some(member.key_range.clone()), &member.value)
+                    }),
+                JsonNode::Array(items) => token
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&index| index < items.len())
+                    .map(|index| {
+                        resolved.path.extend(
+                            items[..=index]
+                                .windows(2)
+                                .map(|pair| pair[0].range.end..pair[1].range.start),
+                        );
+                        (None, &items[index])
+                    }),
+                JsonNode::String | JsonNode::Scalar => None,
+            };
+            let Some((key_range, value)) = next else {
+                return Ok(None);
+            };
+
+            // The brackets of the enclosing object or array
+            resolved
+                .path
+                .push(parent.range.start..parent.range.start + 1);
+            resolved.path.push(parent.range.end - 1..parent.range.end);
+            resolved.key_range = key_range;
+            resolved.value = value;
+        }
+
+        Ok(Some(resolved))
+    }
+}
+
+/// A value matched by a JSON pointer.
+struct Resolved<'a> {
+    value: &'a JsonValue,
+    /// The range of the key of the value, if it is an object member.
+    key_range: Option<Range<usize>>,
+    /// The brackets, keys and separators leading to the value from the root.
+    path: Vec<Range<usize>>,
+}
+
+/// Walks a document which has already been validated by `serde_json`.
+struct Scanner<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips whitespace and the given separator, if present.
+    fn skip_separator(&mut self, separator: u8) {
+        self.skip_whitespace();
+        if self.peek() == Some(separator) {
+            self.pos += 1;
+        }
+        self.skip_whitespace();
+    }
+
+    fn value(&mut self) -> JsonValue {
+        let start = self.pos;
+        let node = match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => {
+                self.string();
+                JsonNode::String
+            }
+            _ => {
+                while !matches!(
+                    self.peek(),
+                    None | Some(b',' | b']' | b'}' | b' ' | b'\t' | b'\n' | b'\r')
+                ) {
+                    self.pos += 1;
+                }
+                JsonNode::Scalar
+            }
+        };
+
+        JsonValue {
+            range: start..self.pos,
+            node,
+        }
+    }
+
+    fn string(&mut self) -> Range<usize> {
+        let start = self.pos;
+        self.pos += 1;
+        while let Some(byte) = self.peek() {
+            self.pos += 1;
+            match byte {
+                b'\\' => self.pos += 1,
+                b'"' => break,
+                _ => {}
+            }
+        }
+        start..self.pos
+    }
+
+    fn object(&mut self) -> JsonNode {
+        let mut members = Vec::new();
+        self.pos += 1;
+        self.skip_whitespace();
+
+        while let Some(b'"') = self.peek() {
+            let key_range = self.string();
+            let key = serde_json::from_slice(&self.input[key_range.clone()])
+                .expect("keys were validated by serde_json");
+            self.skip_separator(b':');
+            let value = self.value();
+            members.push(JsonMember {
+                key,
+                key_range,
+                value,
+            });
+            self.skip_separator(b',');
+        }
+
+        // Closing brace.
+        self.pos += 1;
+        JsonNode::Object(members)
+    }
+
+    fn array(&mut self) -> JsonNode {
+        let mut items = Vec::new();
+        self.pos += 1;
+        self.skip_whitespace();
+
+        while !matches!(self.peek(), None | Some(b']')) {
+            items.push(self.value());
+            self.skip_separator(b',');
+        }
+
+        // Closing bracket.
+        self.pos += 1;
+        JsonNode::Array(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text<'a>(input: &'a str, range: &Range<usize>) -> &'a str {
+        &input[range.clone()]
+    }
+
+    fn members(value: &JsonValue) -> &[JsonMember] {
+        match &value.node {
+            JsonNode::Object(members) => members,
+            node => panic!("expected an object, got {node:?}"),
+        }
+    }
+
+    #[test]
+    fn escaped_strings_are_scanned_whole() {
+        let input = r#"{"say \"hi\"": "a \\\" b", "path\/": "\\", "next": 1}"#;
+        let document = JsonValue::parse(input.as_bytes()).unwrap();
+        let members = members(&document);
+
+        assert_eq!(members.len(), 3);
+        assert_eq!(members[0].key, "say \"hi\"");
+        assert_eq!(text(input, &members[0].key_range), r#""say \"hi\"""#);
+        assert_eq!(text(input, &members[0].value.range), r#""a \\\" b""#);
+        assert_eq!(members[1].key, "path/");
+        assert_eq!(text(input, &members[1].value.range), r#""\\""#);
+        assert_eq!(members[2].key, "next");
+        assert_eq!(text(input, &members[2].value.range), "1");
+    }
+
+    #[test]
+    fn nested_values_are_located() {
+        let input = " { \"a\" : [ 1 , { \"b\" : [ true, null ] } , \"x\" ] , \"c\" : -1.5e3 } ";
+        let document = JsonValue::parse(input.as_bytes()).unwrap();
+
+        assert_eq!(text(input, &document.range), input.trim());
+        let located = |pointer| {
+            document
+                .pointer(pointer)
+                .unwrap()
+                .map(|value| text(input, &value.range))
+        };
+        assert_eq!(located("/a/0"), Some("1"));
+        assert_eq!(located("/a/1/b"), Some("[ true, null ]"));
+        assert_eq!(located("/a/1/b/1"), Some("null"));
+        assert_eq!(located("/a/2"), Some("\"x\""));
+        assert_eq!(located("/c"), Some("-1.5e3"));
+        assert_eq!(located("/a/3"), None);
+        assert_eq!(located("/a/x"), None);
+        assert_eq!(located("/c/0"), None);
+        assert_eq!(located(""), Some(input.trim()));
+    }
+
+    #[test]
+    fn disclosure_ranges_lead_to_the_value() {
+        let input = r#"{"x": 0, "a": {"b": [1, {"c": "d"}, 2]}, "y": true}"#;
+        let document = JsonValue::parse(input.as_bytes()).unwrap();
+        let disclosed = |pointer| {
+            let ranges = document.disclosure_ranges(pointer).unwrap().unwrap();
+            let mut bytes = vec![b'*'; input.len()];
+            for range in ranges {
+                let range = range.start..range.end.min(input.len());
+                bytes[range.clone()].copy_from_slice(&input.as_bytes()[range]);
+            }
+            String::from_utf8(bytes).unwrap()
+        };
+
+        assert_eq!(
+            disclosed("/a/b/1/c"),
+            r#"{********"a": {"b": [*, {"c": "d"}***]}***********}"#
+        );
+        // A number takes the byte after it
+        assert_eq!(
+            disclosed("/a/b/2"),
+            r#"{********"a": {"b": [*, **********, 2]}***********}"#
+        );
+        assert_eq!(
+            disclosed("/x"),
+            r#"{"x": 0,******************************************}"#
+        );
+        assert_eq!(disclosed(""), input);
+        assert_eq!(document.disclosure_ranges("/a/z").unwrap(), None);
+    }
+
+    #[test]
+    fn pointer_tokens_are_unescaped() {
+        let input = r#"{"a/b": 1, "c~d": 2, "~1": 3}"#;
+        let document = JsonValue::parse(input.as_bytes()).unwrap();
+        let located = |pointer| {
+            document
+                .pointer(pointer)
+                .unwrap()
+                .map(|value| text(input, &value.range))
+        };
+
+        assert_eq!(located("/a~1b"), Some("1"));
+        assert_eq!(located("/c~0d"), Some("2"));
+        assert_eq!(located("/~01"), Some("3"));
+    }
+
+    #[test]
+    fn duplicate_keys_resolve_to_the_last_member() {
+        let input = r#"{"a": 1, "a": 2}"#;
+        let document = JsonValue::parse(input.as_bytes()).unwrap();
+        let value = document.pointer("/a").unwrap().unwrap();
+        assert_eq!(text(input, &value.range), "2");
+    }
+
+    #[test]
+    fn invalid_documents_and_pointers_are_refused() {
+        assert!(matches!(
+            JsonValue::parse(br#"{"a": "#),
+            Err(JsonError::Invalid(_))
+        ));
+        let document = JsonValue::parse(b"{}").unwrap();
+        assert!(matches!(
+            document.pointer("a"),
+            Err(JsonError::InvalidPointer(_))
+        ));
+    }
+}
@@ -1,57 +1,134 @@
-use dotenv::dotenv;
-use notary_client::{NotarizationRequest, NotaryClient, NotaryConnection};
-use notary_server::read_pem_file;
-use rustls::{Certificate, RootCertStore};
-use std::env;
+use notary_client::{NotarizationRequest as SessionRequest, NotaryClient, NotaryConnection};
+use tracing::warn;
 
-/// Response object of the /info API
+mod config;
+mod disclose;
+mod envelope;
+mod error;
+mod failover;
+pub mod http;
+mod info;
+mod json;
+pub mod notary;
+mod notary_key;
+mod output;
+mod policy;
+mod preflight;
+mod prover;
+mod range;
+mod redact;
+mod request;
+mod request_file;
+mod retry;
+mod trust;
+mod verifier;
 
-const MAX_SENT_DATA: usize = 1 << 13;
-const MAX_RECV_DATA: usize = 1 << 13;
+pub use config::{
+    ConfigError, ConfigLoader, ConfigSource, DataLimits, NotaryConfig, NotaryEndpoint, CONFIG_KEYS,
+};
+pub use disclose::{DisclosureError, JsonSelector};
+pub use envelope::{
+    EnvelopeError, ProofEnvelope, ProofMetadata, RecordedRedaction, ENVELOPE_VERSION,
+};
+pub use error::OpacityError;
+pub use info::{
+    fetch_info, InfoClient, InfoResponse, VersionMismatch, DEFAULT_INFO_TTL, TLSN_VERSION,
+};
+pub use json::JsonError;
+pub use notary_key::{key_fingerprint, NotaryKeyError, TrustedNotaries};
+pub use output::{read_proof, ProofFormat, ProofIoError, ProofSink};
+pub use policy::{PolicyReport, PolicyRule, RuleResult, VerificationPolicy};
+pub use prover::{notarize, notarize_envelope, NotarizeOptions};
+pub use range::RangeSet;
+pub use redact::{Direction, HttpPart, Redaction, RedactionError, RedactionKind, RedactionMatch};
+pub use request::{NotarizationRequest, RequestBody};
+pub use request_file::{RequestFileError, RequestFormat};
+pub use retry::{RetryClass, RetryPolicy};
+pub use trust::{TrustStore, TrustStoreError};
+pub use verifier::{
+    inspect, verify, verify_with_cert_verifier, verify_with_trust_store, ProofSummary,
+    VerifiedPresentation, VerifiedTranscript, VerifyError,
+};
 
-pub fn read_env_vars() -> (String, u16) {
-    dotenv().ok();
-    let notary_host = env::var("NOTARY_HOST").unwrap_or_else(|_| panic!("$NOTARY_HOST not set"));
-
-    let notary_port_string =
-        env::var("NOTARY_PORT").unwrap_or_else(|_| panic!("$NOTARY_HOST not set"));
+/// A notarization session accepted by a notary.
+pub struct NotarySession {
+    /// Connection to the notary, to run the MPC-TLS protocol over.
+    pub connection: NotaryConnection,
+    /// Id the notary assigned to the session.
+    pub session_id: String,
+    /// The notary which accepted the session.
+    pub notary: NotaryEndpoint,
+}
 
-    let notary_port = notary_port_string
-        .parse::<u16>()
-        .unwrap_or_else(|_| panic!("$NOTARY_PORT must be a number"));
+/// Requests a notarization session from the configured notaries.
+///
+/// The notary is asked for a session of `limits`, which the prover must be configured with too.
+/// Notaries are tried in turn while they cannot be reached or turn the session down, see
+/// [`NotaryEndpoint`].
+pub async fn tls_prover(
+    config: &NotaryConfig,
+    limits: DataLimits,
+) -> Result<NotarySession, OpacityError> {
+    let mut errors = Vec::new();
+    for endpoint in failover::candidates(config).await {
+        match request_session(&config.for_endpoint(&endpoint), limits).await {
+            Ok((connection, session_id)) => {
+                failover::record_success(&endpoint);
+                return Ok(NotarySession {
+                    connection,
+                    session_id,
+                    notary: endpoint,
+                });
+            }
+            Err(err) if failover::fails_over(&err) => {
+                warn!("{err}, trying the next notary");
+                failover::record_failure(&endpoint);
+                errors.push(err);
+            }
+            Err(err) => return Err(err),
+        }
+    }
 
-    (notary_host, notary_port)
+    match errors.len() {
+        1 => Err(errors.remove(0)),
+        _ => Err(OpacityError::AllNotariesFailed(errors)),
+    }
 }
 
-pub async fn tls_prover(host: String, port: u16) -> (NotaryConnection, String) {
-    let mut certificate_file_reader = read_pem_file("fixture/opacityCA.crt").await.unwrap();
-    let mut certificates: Vec<Certificate> = rustls_pemfile::certs(&mut certificate_file_reader)
-        .unwrap()
-        .into_iter()
-        .map(Certificate)
-        .collect();
-    let certificate = certificates.remove(0);
-
-    let mut root_cert_store = RootCertStore::empty();
-    root_cert_store.add(&certificate).unwrap();
+/// Requests a notarization session from the notary of `config`.
+///
+/// Returns the connection to the notary together with the session id assigned to it.
+async fn request_session(
+    config: &NotaryConfig,
+    limits: DataLimits,
+) -> Result<(NotaryConnection, String), OpacityError> {
+    let root_cert_store = config.trust_store.root_cert_store()?;
 
     let notary_client = NotaryClient::builder()
-        .host(&host)
-        .port(port)
+        .host(&config.host)
+        .port(config.port)
+        .enable_tls(config.tls)
         .root_cert_store(root_cert_store)
         .build()
-        .unwrap();
+        .map_err(|err| OpacityError::ClientBuild(err.to_string()))?;
 
-    let notarization_request = NotarizationRequest::builder()
-        .max_sent_data(MAX_SENT_DATA)
-        .max_recv_data(MAX_RECV_DATA)
+    let notarization_request = SessionRequest::builder()
+        .max_sent_data(limits.max_sent_data)
+        .max_recv_data(limits.max_recv_data)
         .build()
-        .unwrap();
+        .map_err(|err| OpacityError::ClientBuild(err.to_string()))?;
 
-    let accepted_request = notary_client
-        .request_notarization(notarization_request)
-        .await
-        .unwrap();
+    let accepted_request = tokio::time::timeout(
+        config.request_timeout,
+        notary_client.request_notarization(notarization_request),
+    )
+    .await
+    .map_err(|_| OpacityError::Timeout {
+        host: config.host.clone(),
+        port: config.port,
+        timeout: config.request_timeout,
+    })?
+    .map_err(|err| OpacityError::from_client_error(err, &config.host, config.port))?;
 
-    (accepted_request.io, accepted_request.id)
+    Ok((accepted_request.io, accepted_request.id))
 }
@@ -0,0 +1,408 @@
+//! Command line interface to notarize requests and verify or inspect the resulting proofs.
+
+use clap::{Args, Parser, Subcommand};
+use eyre::{bail, WrapErr};
+use opacity::{
+    fetch_info, inspect, key_fingerprint, notarize_envelope,
+    notary::{NotaryServerConfig, NotaryTls, DEFAULT_NOTARY_PORT},
+    verify_with_trust_store, NotarizationRequest, NotarizeOptions, NotaryConfig, ProofEnvelope,
+    ProofFormat, ProofSink, TrustStore, TrustedNotaries, VerificationPolicy, VerifiedTranscript,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[command(
+    name = "opacity",
+    version,
+    about = "Notarize HTTPS requests and verify the proofs"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Notarize a request and write the proof.
+    Prove(ProveArgs),
+    /// Verify a proof and print the data it discloses.
+    Verify(VerifyArgs),
+    /// Print the contents of a proof without verifying it.
+    Inspect(InspectArgs),
+    /// Print the JSON Schema of a notarization request.
+    Schema,
+    /// Run a notary.
+    #[command(subcommand)]
+    Notary(NotaryCommand),
+}
+
+#[derive(Subcommand)]
+enum NotaryCommand {
+    /// Serve notarization sessions until interrupted.
+    Serve(ServeArgs),
+    /// Print the version and signing key of the configured notary.
+    Info(InfoArgs),
+}
+
+/// Where the notary config is read from, on top of the environment and `.env`.
+#[derive(Args)]
+struct ConfigArgs {
+    /// TOML config file.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Config override, e.g. `--set port=7047`. May be repeated.
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+}
+
+impl ConfigArgs {
+    fn load(&self) -> eyre::Result<NotaryConfig> {
+        let mut loader = NotaryConfig::loader();
+        if let Some(path) = &self.config {
+            loader = loader.file(path);
+        }
+        for key_value in &self.overrides {
+            loader = loader.set_str(key_value)?;
+        }
+        Ok(loader.load()?)
+    }
+}
+
+#[derive(Args)]
+struct ProveArgs {
+    /// JSON or YAML file holding the request(s) to notarize, or `-` for stdin.
+    #[arg(long, value_name = "PATH")]
+    request: PathBuf,
+    /// File the proof is written to, or `-` for stdout. With several requests, the proof of the
+    /// n-th one is written to `<stem>-<n>.<extension>`.
+    #[arg(long, value_name = "PATH")]
+    out: PathBuf,
+    /// Encoding of the proof: `pretty` or `json` JSON, or binary `cbor`.
+    #[arg(long, value_name = "FORMAT", default_value_t = ProofFormat::PrettyJson)]
+    format: ProofFormat,
+    /// PEM bundle of the CA(s) authenticating the server, instead of the `webpki-roots` set.
+    #[arg(long, value_name = "PATH")]
+    server_ca: Option<PathBuf>,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    /// Proof to verify, in any format, or `-` for stdin.
+    proof: PathBuf,
+    /// PEM file of a trusted notary public key. May be repeated; defaults to the config's keys.
+    #[arg(long, value_name = "PATH")]
+    notary_key: Vec<PathBuf>,
+    /// PEM bundle of the CA(s) authenticating the server, instead of the `webpki-roots` set.
+    #[arg(long, value_name = "PATH")]
+    server_ca: Option<PathBuf>,
+    /// JSON verification policy the proof must satisfy.
+    #[arg(long, value_name = "PATH")]
+    policy: Option<PathBuf>,
+    /// Print the verified presentation as JSON.
+    #[arg(long)]
+    json: bool,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    /// Proof to inspect, in any format, or `-` for stdin.
+    proof: PathBuf,
+    /// Print the contents as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// PEM file of the PKCS#8 P-256 key sessions are signed with.
+    #[arg(long, value_name = "PATH")]
+    signing_key: PathBuf,
+    /// PEM file of the TLS certificate chain of the notary. Without it, plain TCP is served.
+    #[arg(long, value_name = "PATH", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM file of the private key of the TLS certificate.
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Address to listen on.
+    #[arg(long, default_value = "0.0.0.0")]
+    host: String,
+    /// Port to listen on.
+    #[arg(long, default_value_t = DEFAULT_NOTARY_PORT)]
+    port: u16,
+    /// Maximum number of bytes a prover may send to the server in a session.
+    #[arg(long, value_name = "BYTES")]
+    max_sent_data: Option<usize>,
+    /// Maximum number of bytes a prover may receive from the server in a session.
+    #[arg(long, value_name = "BYTES")]
+    max_recv_data: Option<usize>,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+    /// Print the notary's `/info` response as JSON.
+    #[arg(long)]
+    json: bool,
+    #[command(flatten)]
+    config: ConfigArgs,
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
+
+    match Cli::parse().command {
+        Command::Prove(args) => prove(args).await,
+        Command::Verify(args) => verify(args).await,
+        Command::Inspect(args) => inspect_proof(args),
+        Command::Schema => {
+            let schema = NotarizationRequest::json_schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+        Command::Notary(NotaryCommand::Serve(args)) => serve(args).await,
+        Command::Notary(NotaryCommand::Info(args)) => notary_info(args).await,
+    }
+}
+
+async fn prove(args: ProveArgs) -> eyre::Result<()> {
+    let config = args.config.load()?;
+    let requests = NotarizationRequest::load_all(&args.request)?;
+
+    let mut options = NotarizeOptions::new(config);
+    if let Some(server_ca) = args.server_ca {
+        options = options.server_trust_store(TrustStore::PemFile(server_ca));
+    }
+
+    let many = requests.len() > 1;
+    let to_stdout = ProofSink::from_arg(&args.out) == ProofSink::Stdout;
+    if many && to_stdout {
+        bail!("several requests are notarized, write their proofs to files rather than stdout");
+    }
+
+    for (index, request) in requests.iter().enumerate() {
+        let mut sink = if many {
+            ProofSink::Path(numbered_path(&args.out, index))
+        } else {
+            ProofSink::from_arg(&args.out)
+        };
+
+        let envelope = notarize_envelope(request, &options)
+            .await
+            .wrap_err_with(|| format!("failed to notarize request #{index} to {}", request.host))?;
+
+        sink.write(&envelope, args.format)?;
+        eprintln!("The proof has been written to {sink}");
+    }
+    Ok(())
+}
+
+/// Returns `path` with `-<index>` appended to its file stem.
+fn numbered_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}-{index}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{index}"),
+    };
+    path.with_file_name(name)
+}
+
+async fn verify(args: VerifyArgs) -> eyre::Result<()> {
+    let envelope = ProofEnvelope::read(&args.proof)?;
+    let metadata = envelope.metadata;
+
+    let trusted_notaries = if args.notary_key.is_empty() {
+        // Keys fetched from /info are those of the notary which ran the session
+        let mut config = args.config.load()?;
+        if let Some(endpoint) = metadata
+            .notary
+            .as_deref()
+            .and_then(|address| config.find_endpoint(address))
+        {
+            config = config.for_endpoint(&endpoint);
+        }
+        TrustedNotaries::from_config(&config).await?
+    } else {
+        let mut trusted_notaries = TrustedNotaries::new();
+        for path in &args.notary_key {
+            trusted_notaries.add_pem_file(path)?;
+        }
+        trusted_notaries
+    };
+    let server_roots = args
+        .server_ca
+        .map_or(TrustStore::WebPki, TrustStore::PemFile);
+
+    let recorded_fingerprint = metadata.notary_key_fingerprint;
+    let presentation = verify_with_trust_store(envelope.proof, &trusted_notaries, &server_roots)?;
+
+    let fingerprint = key_fingerprint(&presentation.notary_key);
+    if let Some(recorded) = recorded_fingerprint.filter(|recorded| *recorded != fingerprint) {
+        bail!("the proof was signed by notary {fingerprint}, but its envelope names {recorded}");
+    }
+
+    // The policy is checked first, so that a proof failing it is never reported as verified
+    if let Some(path) = &args.policy {
+        let policy: VerificationPolicy = read_json(path)?;
+        let report = policy.evaluate(&presentation);
+        for failure in report.failures() {
+            eprintln!(
+                "Policy rule {:?} failed: {}",
+                failure.rule,
+                failure.reason.as_deref().unwrap_or_default()
+            );
+        }
+        if !report.passed() {
+            bail!("the proof does not satisfy the policy");
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&presentation)?);
+    } else {
+        println!(
+            "Verified a session with {:?} at {}.",
+            presentation.server_name, presentation.time
+        );
+        print_transcripts(&presentation.sent, &presentation.recv);
+    }
+
+    Ok(())
+}
+
+async fn serve(args: ServeArgs) -> eyre::Result<()> {
+    let mut config = NotaryServerConfig::new(args.signing_key);
+    config.host = args.host;
+    config.port = args.port;
+    if let (Some(certificate_path), Some(private_key_path)) = (args.tls_cert, args.tls_key) {
+        config.tls = Some(NotaryTls {
+            certificate_path,
+            private_key_path,
+        });
+    }
+    if let Some(max_sent_data) = args.max_sent_data {
+        config.limits.max_sent_data = max_sent_data;
+    }
+    if let Some(max_recv_data) = args.max_recv_data {
+        config.limits.max_recv_data = max_recv_data;
+    }
+
+    let notary = opacity::notary::spawn(config).await?;
+    eprintln!(
+        "Notary listening on {}, signing with key {}",
+        notary.addr(),
+        key_fingerprint(notary.public_key())
+    );
+
+    tokio::select! {
+        result = notary.wait() => Ok(result?),
+        result = tokio::signal::ctrl_c() => Ok(result?),
+    }
+}
+
+async fn notary_info(args: InfoArgs) -> eyre::Result<()> {
+    let config = args.config.load()?;
+    let info = fetch_info(&config).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        println!("Notary:      {}:{}", config.host, config.port);
+        println!("Version:     {}", info.version);
+        println!(
+            "Commit:      {} ({})",
+            info.git_commit_hash, info.git_commit_timestamp
+        );
+        println!("Signing key: {}", key_fingerprint(&info.signing_key()?));
+    }
+    if let Some(mismatch) = info.version_mismatch() {
+        eprintln!("Warning: {mismatch}");
+    }
+    Ok(())
+}
+
+fn inspect_proof(args: InspectArgs) -> eyre::Result<()> {
+    let envelope = ProofEnvelope::read(&args.proof)?;
+    let metadata = envelope.metadata;
+    let summary = inspect(envelope.proof)?;
+
+    if args.json {
+        let inspected = serde_json::json!({
+            "version": envelope.version,
+            "metadata": metadata,
+            "summary": summary,
+        });
+        println!("{}", serde_json::to_string_pretty(&inspected)?);
+        return Ok(());
+    }
+
+    println!("UNVERIFIED: neither the notary signature nor the server identity was checked.");
+    println!("Envelope:    version {}", envelope.version);
+    println!(
+        "Created:     {} by opacity {}",
+        metadata.created_at, metadata.library_version
+    );
+    println!(
+        "Notary:      {}",
+        metadata.notary.as_deref().unwrap_or("unknown")
+    );
+    println!(
+        "Notary key:  {}",
+        metadata
+            .notary_key_fingerprint
+            .as_deref()
+            .unwrap_or("unknown")
+    );
+    println!(
+        "Request:     {} https://{}:{}{}",
+        metadata.request.method,
+        metadata.request.host,
+        metadata.request.port,
+        metadata.request.path
+    );
+    println!(
+        "Redactions:  {}",
+        serde_json::to_string(&metadata.redactions)?
+    );
+    println!(
+        "Disclosed:   {}",
+        serde_json::to_string(&metadata.disclose)?
+    );
+    println!("Server name: {}", summary.server_name);
+    println!("Time:        {}", summary.time);
+    println!("Signed:      {}", summary.signed);
+    for (direction, transcript) in [("Sent", &summary.sent), ("Received", &summary.recv)] {
+        let revealed = transcript.redacted.complement(0..transcript.data.len());
+        println!(
+            "{direction}: {} bytes, {} revealed in ranges {:?}",
+            transcript.data.len(),
+            revealed.len(),
+            revealed.iter().collect::<Vec<_>>()
+        );
+    }
+    print_transcripts(&summary.sent, &summary.recv);
+    Ok(())
+}
+
+fn print_transcripts(sent: &VerifiedTranscript, recv: &VerifiedTranscript) {
+    println!("Bytes which were not disclosed are shown as X.");
+    println!();
+    println!("Bytes sent:");
+    println!();
+    print!("{}", sent.to_string_lossy(b'X'));
+    println!();
+    println!("Bytes received:");
+    println!();
+    println!("{}", recv.to_string_lossy(b'X'));
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> eyre::Result<T> {
+    let data =
+        std::fs::read(path).wrap_err_with(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_slice(&data).wrap_err_with(|| format!("failed to parse {}", path.display()))
+}
@@ -0,0 +1,312 @@
+//! Runs a notary server in-process, for teams self-hosting a notary alongside their provers.
+
+use crate::{config::DataLimits, NotaryConfig};
+use elliptic_curve::pkcs8::{DecodePrivateKey, EncodePublicKey, LineEnding};
+use notary_server::{
+    AuthorizationProperties, LoggingProperties, NotarizationProperties, NotaryServerProperties,
+    NotarySigningKeyProperties, ServerProperties, TLSProperties,
+};
+use std::{
+    io::{self, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tempfile::NamedTempFile;
+use tokio::{net::TcpStream, task::JoinHandle};
+
+/// Default port of a notary.
+pub const DEFAULT_NOTARY_PORT: u16 = 7047;
+/// How long [`spawn`] waits for the notary to listen.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors raised while running a notary.
+#[derive(Debug, thiserror::Error)]
+pub enum ServeError {
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("invalid signing key {path}, expected a PKCS#8 PEM P-256 key: {reason}")]
+    SigningKey { path: PathBuf, reason: String },
+    #[error("failed to write the notary public key to {path}: {source}")]
+    PublicKey { path: PathBuf, source: io::Error },
+    #[error("failed to pick a free port: {0}")]
+    FreePort(io::Error),
+    #[error("notary did not listen on {addr} within {STARTUP_TIMEOUT:?}")]
+    Startup { addr: SocketAddr },
+    #[error("invalid listen address {0:?}")]
+    Address(String),
+    #[error("notary server failed: {0}")]
+    Server(String),
+}
+
+/// Certificate and key a notary authenticates itself with over TLS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotaryTls {
+    /// PEM file of the certificate chain, leaf first.
+    pub certificate_path: PathBuf,
+    /// PEM file of the certificate's private key.
+    pub private_key_path: PathBuf,
+}
+
+/// Settings of a notary run by [`spawn`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotaryServerConfig {
+    /// Name of the notary, as reported on its `/info` endpoint.
+    pub name: String,
+    /// Address to listen on.
+    pub host: String,
+    /// Port to listen on, or 0 to pick a free one.
+    pub port: u16,
+    /// PEM file of the PKCS#8 P-256 key the notary signs sessions with.
+    pub signing_key_path: PathBuf,
+    /// TLS certificate of the notary, or `None` to serve plain TCP, e.g. behind a proxy
+    /// terminating TLS.
+    pub tls: Option<NotaryTls>,
+    /// Largest sessions the notary accepts.
+    pub limits: DataLimits,
+}
+
+impl NotaryServerConfig {
+    /// Creates a config for a notary signing with the key in `signing_key_path`, listening on
+    /// every interface on [`DEFAULT_NOTARY_PORT`] without TLS.
+    pub fn new(signing_key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: "opacity-notary".to_string(),
+            host: "0.0.0.0".to_string(),
+            port: DEFAULT_NOTARY_PORT,
+            signing_key_path: signing_key_path.into(),
+            tls: None,
+            limits: DataLimits::default(),
+        }
+    }
+}
+
+/// A notary running in the background of the current tokio runtime.
+///
+/// The notary stops when the handle is dropped.
+#[derive(Debug)]
+pub struct NotaryHandle {
+    addr: SocketAddr,
+    tls: bool,
+    public_key: p256::PublicKey,
+    /// The public key file the notary reads, removed when the handle is dropped.
+    public_key_file: NamedTempFile,
+    task: JoinHandle<Result<(), ServeError>>,
+}
+
+impl NotaryHandle {
+    /// Address the notary listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Port the notary listens on, useful when it was picked by [`spawn`].
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Key the notary signs sessions with.
+    pub fn public_key(&self) -> &p256::PublicKey {
+        &self.public_key
+    }
+
+    /// Returns a config reaching this notary through `host`, with its signing key pinned.
+    ///
+    /// With TLS, the trust store of the config must still be set to authenticate the notary's
+    /// certificate.
+    pub fn client_config(&self, host: impl Into<String>) -> NotaryConfig {
+        let mut config = NotaryConfig::new(host, self.port());
+        config.tls = self.tls;
+        config.public_key = self.public_key.to_public_key_pem(LineEnding::LF).ok();
+        config
+    }
+
+    /// Waits for the notary to stop, which only happens if it fails.
+    pub async fn wait(mut self) -> Result<(), ServeError> {
+        (&mut self.task)
+            .await
+            .map_err(|err| ServeError::Server(err.to_string()))?
+    }
+}
+
+impl Drop for NotaryHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Starts a notary with `config` and waits for it to listen.
+///
+/// The public key served on `/info` is derived from the signing key, and written to a temporary
+/// file, only readable by the current user, for the notary to read.
+pub async fn spawn(config: NotaryServerConfig) -> Result<NotaryHandle, ServeError> {
+    let public_key = read_signing_key(&config.signing_key_path)?;
+
+    let ip = config
+        .host
+        .parse()
+        .map_err(|_| ServeError::Address(config.host.clone()))?;
+    let port = match config.port {
+        0 => free_port(ip)?,
+        port => port,
+    };
+    let addr = SocketAddr::new(ip, port);
+
+    let public_key_pem = public_key
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(|err| ServeError::SigningKey {
+            path: config.signing_key_path.clone(),
+            reason: err.to_string(),
+        })?;
+    let public_key_file = write_public_key(&public_key_pem)?;
+
+    let properties = server_properties(&config, port, public_key_file.path());
+    let task = tokio::spawn(async move {
+        notary_server::run_server(&properties)
+            .await
+            .map_err(|err| ServeError::Server(err.to_string()))
+    });
+    let mut handle = NotaryHandle {
+        addr,
+        tls: config.tls.is_some(),
+        public_key,
+        public_key_file,
+        task,
+    };
+
+    wait_until_listening(&mut handle).await?;
+    Ok(handle)
+}
+
+fn read_signing_key(path: &Path) -> Result<p256::PublicKey, ServeError> {
+    let pem = std::fs::read_to_string(path).map_err(|source| ServeError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let secret_key =
+        p256::SecretKey::from_pkcs8_pem(&pem).map_err(|err| ServeError::SigningKey {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?;
+    Ok(secret_key.public_key())
+}
+
+/// Writes `pem` to a new temporary file, which is created exclusively so that another user cannot
+/// have planted it.
+fn write_public_key(pem: &str) -> Result<NamedTempFile, ServeError> {
+    let mut file = tempfile::Builder::new()
+        .prefix("opacity-notary-")
+        .suffix(".pub")
+        .tempfile()
+        .map_err(|source| ServeError::PublicKey {
+            path: std::env::temp_dir(),
+            source,
+        })?;
+    file.write_all(pem.as_bytes())
+        .and_then(|()| file.flush())
+        .map_err(|source| ServeError::PublicKey {
+            path: file.path().to_path_buf(),
+            source,
+        })?;
+    Ok(file)
+}
+
+fn server_properties(
+    config: &NotaryServerConfig,
+    port: u16,
+    public_key_path: &Path,
+) -> NotaryServerProperties {
+    let path = |path: &Path| path.to_string_lossy().into_owned();
+    let tls = match &config.tls {
+        Some(tls) => TLSProperties {
+            enabled: true,
+            private_key_pem_path: path(&tls.private_key_path),
+            certificate_pem_path: path(&tls.certificate_path),
+        },
+        None => TLSProperties {
+            enabled: false,
+            private_key_pem_path: String::new(),
+            certificate_pem_path: String::new(),
+        },
+    };
+
+    NotaryServerProperties {
+        server: ServerProperties {
+            name: config.name.clone(),
+            host: config.host.clone(),
+            port,
+            html_info: String::new(),
+        },
+        notarization: NotarizationProperties {
+            max_sent_data: config.limits.max_sent_data,
+            max_recv_data: config.limits.max_recv_data,
+        },
+        tls,
+        notary_key: NotarySigningKeyProperties {
+            private_key_pem_path: path(&config.signing_key_path),
+            public_key_pem_path: path(public_key_path),
+        },
+        logging: LoggingProperties {
+            level: "INFO".to_string(),
+            filter: None,
+        },
+        authorization: AuthorizationProperties {
+            enabled: false,
+            whitelist_csv_path: String::new(),
+        },
+    }
+}
+
+/// Returns a port which is free on `ip` at the time of the call.
+fn free_port(ip: std::net::IpAddr) -> Result<u16, ServeError> {
+    let listener = TcpListener::bind((ip, 0)).map_err(ServeError::FreePort)?;
+    Ok(listener.local_addr().map_err(ServeError::FreePort)?.port())
+}
+
+async fn wait_until_listening(handle: &mut NotaryHandle) -> Result<(), ServeError> {
+    // A notary listening on every interface is reached through the loopback one
+    let mut addr = handle.addr;
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+
+    let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        if handle.task.is_finished() {
+            return match (&mut handle.task).await {
+                Ok(Err(err)) => Err(err),
+                Ok(Ok(())) => Err(ServeError::Server("stopped on startup".to_string())),
+                Err(err) => Err(ServeError::Server(err.to_string())),
+            };
+        }
+        if TcpStream::connect(addr).await.is_ok() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    Err(ServeError::Startup { addr: handle.addr })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_key_file_is_private_and_removed_on_drop() {
+        let file = write_public_key("public key").unwrap();
+        let path = file.path().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "public key");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o077, 0, "{mode:o}");
+        }
+
+        drop(file);
+        assert!(!path.exists());
+    }
+}
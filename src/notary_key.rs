@@ -0,0 +1,155 @@
+use crate::{info::InfoClient, trust::TrustStoreError, NotaryConfig};
+use elliptic_curve::pkcs8::{DecodePublicKey, EncodePublicKey};
+use std::path::{Path, PathBuf};
+
+const PEM_BEGIN: &str = "-----BEGIN PUBLIC KEY-----";
+const PEM_END: &str = "-----END PUBLIC KEY-----";
+
+/// Errors raised while gathering the notary keys trusted by a verifier.
+#[derive(Debug, thiserror::Error)]
+pub enum NotaryKeyError {
+    #[error("failed to read notary public key {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid notary public key: {0}")]
+    InvalidKey(String),
+    #[error("no notary public key is trusted: pin one with $NOTARY_PUBLIC_KEY_PATH or $NOTARY_PUBLIC_KEY, or opt in to $NOTARY_FETCH_PUBLIC_KEY")]
+    NoKeys,
+    #[error("failed to load the notary CA: {0}")]
+    TrustStore(#[from] TrustStoreError),
+    #[error("failed to fetch the notary /info: {0}")]
+    Fetch(#[from] reqwest::Error),
+    #[error("refusing to fetch the key of notary {host}:{port} without TLS: set $NOTARY_TLS=true or pin the key with $NOTARY_PUBLIC_KEY_PATH")]
+    InsecureFetch { host: String, port: u16 },
+}
+
+impl NotaryKeyError {
+    /// Fails unless the notary of `config` is reached over TLS, as a key fetched over plain HTTP
+    /// could have been swapped by anyone on the path.
+    pub(crate) fn require_tls(config: &NotaryConfig) -> Result<(), Self> {
+        if config.tls {
+            return Ok(());
+        }
+        Err(NotaryKeyError::InsecureFetch {
+            host: config.host.clone(),
+            port: config.port,
+        })
+    }
+}
+
+/// Set of notary public keys a verifier accepts proofs from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedNotaries {
+    keys: Vec<p256::PublicKey>,
+}
+
+impl TrustedNotaries {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trusts `key`.
+    pub fn add(&mut self, key: p256::PublicKey) {
+        if !self.keys.contains(&key) {
+            self.keys.push(key);
+        }
+    }
+
+    /// Trusts every `PUBLIC KEY` block of a PEM document.
+    pub fn add_pem(&mut self, pem: &str) -> Result<(), NotaryKeyError> {
+        let mut rest = pem;
+        let mut found = false;
+        while let Some(start) = rest.find(PEM_BEGIN) {
+            let end = rest[start..]
+                .find(PEM_END)
+                .map(|end| start + end + PEM_END.len())
+                .ok_or_else(|| NotaryKeyError::InvalidKey("unterminated PEM block".to_string()))?;
+            let key = p256::PublicKey::from_public_key_pem(&rest[start..end])
+                .map_err(|err| NotaryKeyError::InvalidKey(err.to_string()))?;
+            self.add(key);
+            found = true;
+            rest = &rest[end..];
+        }
+
+        if !found {
+            return Err(NotaryKeyError::InvalidKey(
+                "no PUBLIC KEY block found".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Trusts every public key of a PEM file.
+    pub fn add_pem_file(&mut self, path: impl AsRef<Path>) -> Result<(), NotaryKeyError> {
+        let path = path.as_ref();
+        let pem = std::fs::read_to_string(path).map_err(|source| NotaryKeyError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        self.add_pem(&pem)
+    }
+
+    /// Gathers the keys pinned in `config`.
+    ///
+    /// The notary's `/info` endpoint is only queried if `config.fetch_public_key` is set, in which
+    /// case the connection must use TLS, is authenticated with the config's trust store and the
+    /// response is cached by [`InfoClient::shared`]. Fails if no key ends up being trusted.
+    pub async fn from_config(config: &NotaryConfig) -> Result<Self, NotaryKeyError> {
+        let mut notaries = TrustedNotaries::new();
+
+        if let Some(pem) = &config.public_key {
+            notaries.add_pem(pem)?;
+        }
+        if let Some(path) = &config.public_key_path {
+            notaries.add_pem_file(path)?;
+        }
+        if config.fetch_public_key {
+            NotaryKeyError::require_tls(config)?;
+            let info = InfoClient::shared().info(config).await?;
+            notaries.add(info.signing_key()?);
+        }
+
+        if notaries.is_empty() {
+            return Err(NotaryKeyError::NoKeys);
+        }
+        Ok(notaries)
+    }
+
+    /// Whether `key` is trusted.
+    pub fn contains(&self, key: &p256::PublicKey) -> bool {
+        self.keys.contains(key)
+    }
+
+    /// Iterates over the trusted keys.
+    pub fn keys(&self) -> impl Iterator<Item = &p256::PublicKey> {
+        self.keys.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Returns the fingerprint of a notary key: `sha256:` followed by the hex SHA-256 digest of its
+/// DER encoded SubjectPublicKeyInfo.
+pub fn key_fingerprint(key: &p256::PublicKey) -> String {
+    let der = key
+        .to_public_key_der()
+        .expect("P-256 public keys always encode");
+    let digest = ring::digest::digest(&ring::digest::SHA256, der.as_bytes());
+    let hex: String = digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256:{hex}")
+}
+
+impl From<p256::PublicKey> for TrustedNotaries {
+    fn from(key: p256::PublicKey) -> Self {
+        Self { keys: vec![key] }
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Errors raised while encoding, writing or reading a proof.
+#[derive(Debug, thiserror::Error)]
+pub enum ProofIoError {
+    #[error("failed to write {path}: {source}")]
+    Write { path: PathBuf, source: io::Error },
+    #[error("failed to read {path}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("failed to write to stdout: {0}")]
+    Stdout(io::Error),
+    #[error("failed to read from stdin: {0}")]
+    Stdin(io::Error),
+    #[error("invalid JSON proof: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to encode the proof as CBOR: {0}")]
+    CborEncode(#[from] ciborium::ser::Error<io::Error>),
+    #[error("invalid CBOR proof: {0}")]
+    CborDecode(#[from] ciborium::de::Error<io::Error>),
+    #[error("unknown proof format {0:?}, expected `pretty`, `json` or `cbor`")]
+    UnknownFormat(String),
+}
+
+/// Encoding of a proof.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProofFormat {
+    /// Indented JSON.
+    #[default]
+    PrettyJson,
+    /// JSON without whitespace.
+    Json,
+    /// CBOR (RFC 8949), much smaller for large transcripts.
+    Cbor,
+}
+
+impl ProofFormat {
+    /// Encodes `value` in this format.
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, ProofIoError> {
+        let mut data = Vec::new();
+        match self {
+            ProofFormat::PrettyJson => serde_json::to_writer_pretty(&mut data, value)?,
+            ProofFormat::Json => serde_json::to_writer(&mut data, value)?,
+            ProofFormat::Cbor => ciborium::into_writer(value, &mut data)?,
+        }
+        Ok(data)
+    }
+
+    /// Tells the format of encoded data apart: JSON proofs are objects, so anything else is
+    /// taken to be CBOR.
+    pub fn detect(data: &[u8]) -> Self {
+        match data.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{') => ProofFormat::Json,
+            _ => ProofFormat::Cbor,
+        }
+    }
+
+    /// Decodes `data`, whatever format it was encoded in.
+    pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, ProofIoError> {
+        match Self::detect(data) {
+            ProofFormat::PrettyJson | ProofFormat::Json => Ok(serde_json::from_slice(data)?),
+            ProofFormat::Cbor => Ok(ciborium::from_reader(data)?),
+        }
+    }
+}
+
+impl FromStr for ProofFormat {
+    type Err = ProofIoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(ProofFormat::PrettyJson),
+            "json" => Ok(ProofFormat::Json),
+            "cbor" => Ok(ProofFormat::Cbor),
+            _ => Err(ProofIoError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ProofFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofFormat::PrettyJson => write!(f, "pretty"),
+            ProofFormat::Json => write!(f, "json"),
+            ProofFormat::Cbor => write!(f, "cbor"),
+        }
+    }
+}
+
+/// Destination of an encoded proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofSink {
+    /// A file, replaced if it exists.
+    Path(PathBuf),
+    /// The standard output.
+    Stdout,
+    /// A buffer, which every write appends to.
+    Memory(Vec<u8>),
+}
+
+impl ProofSink {
+    /// Returns the sink for a command line argument, where `-` stands for stdout.
+    pub fn from_arg(arg: impl Into<PathBuf>) -> Self {
+        let path = arg.into();
+        if path == Path::new("-") {
+            ProofSink::Stdout
+        } else {
+            ProofSink::Path(path)
+        }
+    }
+
+    /// Encodes `value` in `format` and writes it to the sink.
+    pub fn write<T: Serialize>(
+        &mut self,
+        value: &T,
+        format: ProofFormat,
+    ) -> Result<(), ProofIoError> {
+        let data = format.encode(value)?;
+        match self {
+            ProofSink::Path(path) => {
+                std::fs::write(&*path, data).map_err(|source| ProofIoError::Write {
+                    path: path.clone(),
+                    source,
+                })
+            }
+            ProofSink::Stdout => {
+                let mut stdout = io::stdout().lock();
+                stdout
+                    .write_all(&data)
+                    .and_then(|()| stdout.flush())
+                    .map_err(ProofIoError::Stdout)
+            }
+            ProofSink::Memory(buffer) => {
+                buffer.extend_from_slice(&data);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for ProofSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofSink::Path(path) => write!(f, "{}", path.display()),
+            ProofSink::Stdout => write!(f, "stdout"),
+            ProofSink::Memory(_) => write!(f, "memory"),
+        }
+    }
+}
+
+/// Reads and decodes a proof from `path`, or from stdin if `path` is `-`, detecting its format.
+pub fn read_proof<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, ProofIoError> {
+    ProofFormat::decode(&read_input(path.as_ref())?)
+}
+
+/// Reads the whole of `path`, or of stdin if `path` is `-`.
+pub(crate) fn read_input(path: &Path) -> Result<Vec<u8>, ProofIoError> {
+    if path == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin()
+            .read_to_end(&mut data)
+            .map_err(ProofIoError::Stdin)?;
+        Ok(data)
+    } else {
+        std::fs::read(path).map_err(|source| ProofIoError::Read {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [ProofFormat; 3] = [
+        ProofFormat::PrettyJson,
+        ProofFormat::Json,
+        ProofFormat::Cbor,
+    ];
+
+    fn value() -> serde_json::Value {
+        serde_json::json!({"version": "1.0", "data": [1, 2, 3], "text": "{not json}"})
+    }
+
+    #[test]
+    fn detect_tells_json_from_cbor() {
+        assert_eq!(ProofFormat::detect(b"{}"), ProofFormat::Json);
+        assert_eq!(ProofFormat::detect(b" \n\t{\"a\": 1}"), ProofFormat::Json);
+        for format in FORMATS {
+            let detected = ProofFormat::detect(&format.encode(&value()).unwrap());
+            let expected = match format {
+                ProofFormat::Cbor => ProofFormat::Cbor,
+                _ => ProofFormat::Json,
+            };
+            assert_eq!(detected, expected, "{format}");
+        }
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for format in FORMATS {
+            let encoded = format.encode(&value()).unwrap();
+            let decoded: serde_json::Value = ProofFormat::decode(&encoded).unwrap();
+            assert_eq!(decoded, value(), "{format}");
+        }
+    }
+
+    #[test]
+    fn invalid_data_is_refused() {
+        let json = ProofFormat::decode::<serde_json::Value>(b"{\"a\":");
+        assert!(matches!(json, Err(ProofIoError::Json(_))), "{json:?}");
+        let cbor = ProofFormat::decode::<serde_json::Value>(&[0xff]);
+        assert!(matches!(cbor, Err(ProofIoError::CborDecode(_))), "{cbor:?}");
+    }
+
+    #[test]
+    fn memory_sink_appends_every_write() {
+        let mut sink = ProofSink::Memory(Vec::new());
+        sink.write(&value(), ProofFormat::Json).unwrap();
+        sink.write(&value(), ProofFormat::Cbor).unwrap();
+
+        let ProofSink::Memory(buffer) = sink else {
+            unreachable!()
+        };
+        let json = ProofFormat::Json.encode(&value()).unwrap();
+        let cbor = ProofFormat::Cbor.encode(&value()).unwrap();
+        assert_eq!(buffer, [json, cbor].concat());
+    }
+
+    #[test]
+    fn path_sink_is_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proof.cbor");
+        let mut sink = ProofSink::from_arg(&path);
+        assert_eq!(sink, ProofSink::Path(path.clone()));
+
+        sink.write(&value(), ProofFormat::Cbor).unwrap();
+        assert_eq!(read_proof::<serde_json::Value>(&path).unwrap(), value());
+        assert!(matches!(
+            read_proof::<serde_json::Value>(dir.path().join("missing")),
+            Err(ProofIoError::Read { .. })
+        ));
+    }
+
+    #[test]
+    fn formats_and_sinks_parse_from_arguments() {
+        for format in FORMATS {
+            assert_eq!(format.to_string().parse::<ProofFormat>().unwrap(), format);
+        }
+        assert!(matches!(
+            "yaml".parse::<ProofFormat>(),
+            Err(ProofIoError::UnknownFormat(format)) if format == "yaml"
+        ));
+        assert_eq!(ProofSink::from_arg("-"), ProofSink::Stdout);
+    }
+}
@@ -0,0 +1,824 @@
+use crate::{
+    http::{HttpMessage, StartLine},
+    range::RangeSet,
+    VerifiedPresentation, VerifiedTranscript,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Default tolerance for sessions timestamped in the future, to absorb clock skew.
+const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+/// Requirements a verified session must meet to be accepted.
+///
+/// Every rule is optional; an empty policy accepts any verified session.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerificationPolicy {
+    /// Server names the session may have been held with, compared case-insensitively.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_servers: Vec<String>,
+    /// Maximum age of the session, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_secs: Option<u64>,
+    /// How far in the future the session time may be, in seconds.
+    #[serde(default = "default_max_clock_skew_secs")]
+    pub max_clock_skew_secs: u64,
+    /// Expected HTTP method of the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Expected path of the request, without the query string.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// JSON pointers of response body fields which must be disclosed, together with the brackets
+    /// and keys leading to them from the root of the body.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_fields: Vec<String>,
+}
+
+impl Default for VerificationPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_servers: Vec::new(),
+            max_age_secs: None,
+            max_clock_skew_secs: DEFAULT_MAX_CLOCK_SKEW_SECS,
+            method: None,
+            path: None,
+            required_fields: Vec::new(),
+        }
+    }
+}
+
+fn default_max_clock_skew_secs() -> u64 {
+    DEFAULT_MAX_CLOCK_SKEW_SECS
+}
+
+/// A rule of a [`VerificationPolicy`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyRule {
+    AllowedServer,
+    MaxAge,
+    NotInFuture,
+    Method,
+    Path,
+    RequiredField(String),
+}
+
+/// Outcome of a single [`PolicyRule`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RuleResult {
+    pub rule: PolicyRule,
+    pub passed: bool,
+    /// Why the rule failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Outcome of every rule of a [`VerificationPolicy`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PolicyReport {
+    pub results: Vec<RuleResult>,
+}
+
+impl PolicyReport {
+    /// Whether every rule passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.passed)
+    }
+
+    /// Iterates over the rules which failed.
+    pub fn failures(&self) -> impl Iterator<Item = &RuleResult> {
+        self.results.iter().filter(|result| !result.passed)
+    }
+
+    fn push(&mut self, rule: PolicyRule, outcome: Result<(), String>) {
+        self.results.push(RuleResult {
+            rule,
+            passed: outcome.is_ok(),
+            reason: outcome.err(),
+        });
+    }
+}
+
+impl VerificationPolicy {
+    /// Evaluates the policy against a verified session, at the current time.
+    pub fn evaluate(&self, presentation: &VerifiedPresentation) -> PolicyReport {
+        self.evaluate_at(presentation, Utc::now())
+    }
+
+    /// Evaluates the policy against a verified session, as if the current time was `now`.
+    pub fn evaluate_at(
+        &self,
+        presentation: &VerifiedPresentation,
+        now: DateTime<Utc>,
+    ) -> PolicyReport {
+        let mut report = PolicyReport::default();
+
+        if !self.allowed_servers.is_empty() {
+            let allowed = self
+                .allowed_servers
+                .iter()
+                .any(|server| server.eq_ignore_ascii_case(&presentation.server_name));
+            report.push(
+                PolicyRule::AllowedServer,
+                check(allowed, || {
+                    format!("server {:?} is not allowed", presentation.server_name)
+                }),
+            );
+        }
+
+        if let Some(max_age_secs) = self.max_age_secs {
+            let age = now - presentation.time;
+            report.push(
+                PolicyRule::MaxAge,
+                check(age <= seconds(max_age_secs), || {
+                    format!("session is {}s old", age.num_seconds())
+                }),
+            );
+        }
+
+        let ahead = presentation.time - now;
+        report.push(
+            PolicyRule::NotInFuture,
+            check(ahead <= seconds(self.max_clock_skew_secs), || {
+                format!("session is {}s in the future", ahead.num_seconds())
+            }),
+        );
+
+        if self.method.is_some() || self.path.is_some() {
+            let request_line = request_line(&presentation.sent);
+            if let Some(expected) = &self.method {
+                let outcome =
+                    request_line
+                        .as_ref()
+                        .map_err(Clone::clone)
+                        .and_then(|(method, _)| {
+                            check(method.eq_ignore_ascii_case(expected), || {
+                                format!("request method is {method:?}")
+                            })
+                        });
+                report.push(PolicyRule::Method, outcome);
+            }
+            if let Some(expected) = &self.path {
+                let outcome =
+                    request_line
+                        .as_ref()
+                        .map_err(Clone::clone)
+                        .and_then(|(_, target)| {
+                            let path = target.split('?').next().unwrap_or_default();
+                            check(path == expected, || format!("request path is {path:?}"))
+                        });
+                report.push(PolicyRule::Path, outcome);
+            }
+        }
+
+        for pointer in &self.required_fields {
+            report.push(
+                PolicyRule::RequiredField(pointer.clone()),
+                field_disclosed(&presentation.recv, pointer),
+            );
+        }
+
+        report
+    }
+}
+
+fn check(passed: bool, reason: impl FnOnce() -> String) -> Result<(), String> {
+    if passed {
+        Ok(())
+    } else {
+        Err(reason())
+    }
+}
+
+fn seconds(secs: u64) -> Duration {
+    Duration::seconds(i64::try_from(secs).unwrap_or(i64::MAX))
+}
+
+/// Returns the disclosed bytes of `range`, or an error naming `what` if any of them is redacted.
+fn disclosed<'a>(
+    transcript: &'a VerifiedTranscript,
+    range: Range<usize>,
+    what: &str,
+) -> Result<&'a str, String> {
+    let redacted = RangeSet::from_iter([range.clone()]).intersection(&transcript.redacted);
+    if !redacted.is_empty() {
+        return Err(format!("{what} is redacted"));
+    }
+    std::str::from_utf8(&transcript.data[range]).map_err(|_| format!("{what} is not UTF-8"))
+}
+
+/// Returns the method and target of the request, provided they are disclosed.
+fn request_line(sent: &VerifiedTranscript) -> Result<(String, String), String> {
+    let message = HttpMessage::parse_request(&sent.data)
+        .map_err(|err| format!("cannot parse the request: {err}"))?;
+    let StartLine::Request { method, target, .. } = message.start else {
+        return Err("missing request line".to_string());
+    };
+    Ok((
+        disclosed(sent, method, "request method")?.to_string(),
+        disclosed(sent, target, "request target")?.to_string(),
+    ))
+}
+
+/// Checks that the response body field at `pointer` is disclosed.
+///
+/// The pointer is resolved by reading the body from its root. The brackets of the enclosing
+/// objects and arrays, the keys leading to the field and the field itself must be disclosed, and a
+/// number or literal must be followed by a disclosed byte, which shows that it is whole. Other
+/// members and items may be redacted, as when the request selects the fields to disclose. What a
+/// redacted part holds cannot be read, so a redacted part of an object is taken to hold whole
+/// members, and a redacted part of an array between two disclosed separators to hold one item.
+/// Array items are therefore counted by their separators. A key repeated in an object resolves to
+/// its last member, as with `serde_json`.
+fn field_disclosed(recv: &VerifiedTranscript, pointer: &str) -> Result<(), String> {
+    let message = HttpMessage::parse_response(&recv.data)
+        .map_err(|err| format!("cannot parse the response: {err}"))?;
+    let body = message.decoded_body(&recv.data);
+
+    // Redactions of the body, moved to the positions of the decoded body
+    let mut redacted = RangeSet::new();
+    let mut offset = 0;
+    for chunk in &message.body_chunks {
+        let chunk_redacted = RangeSet::from_iter([chunk.clone()]).intersection(&recv.redacted);
+        redacted.extend(
+            chunk_redacted
+                .iter()
+                .map(|range| range.start - chunk.start + offset..range.end - chunk.start + offset),
+        );
+        offset += chunk.len();
+    }
+
+    let tokens = match pointer.strip_prefix('/') {
+        _ if pointer.is_empty() => Vec::new(),
+        Some(tokens) => tokens
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+        None => return Err(format!("invalid JSON pointer {pointer:?}")),
+    };
+
+    let mut reader = DisclosedJson {
+        input: &body,
+        redacted: &redacted,
+        pos: 0,
+        depth: 0,
+    };
+    reader.skip_whitespace();
+    check(reader.find(&tokens)?, || "field is missing".to_string())
+}
+
+const HIDDEN: &str = "field is redacted or its enclosing structure is hidden";
+
+/// How deeply arrays and objects may nest, as with `serde_json`.
+const MAX_DEPTH: usize = 128;
+
+/// An item of an array of which some bytes are redacted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    /// A disclosed item, starting at the given position.
+    Disclosed(usize),
+    /// A redacted part between two separators.
+    Hidden,
+    /// A redacted part which does not lie between two separators, and so may hold any number of
+    /// items.
+    Uncounted,
+}
+
+/// The key of an object member, with the position of its value or `None` if the value is redacted.
+type Member = (String, Option<usize>);
+
+/// Reads a JSON document of which some bytes are redacted.
+struct DisclosedJson<'a> {
+    input: &'a [u8],
+    redacted: &'a RangeSet,
+    pos: usize,
+    depth: usize,
+}
+
+impl DisclosedJson<'_> {
+    fn hidden(&self) -> bool {
+        self.redacted.contains(self.pos)
+    }
+
+    /// Explains why the document cannot be read. Whether it is valid JSON can only be told when
+    /// none of it is redacted.
+    fn malformed(&self) -> String {
+        if self.redacted.is_empty() {
+            "response body is not valid JSON".to_string()
+        } else {
+            HIDDEN.to_string()
+        }
+    }
+
+    /// Returns the byte at the current position, or `None` at the end of the document.
+    fn peek(&self) -> Result<Option<u8>, String> {
+        if self.hidden() {
+            return Err(HIDDEN.to_string());
+        }
+        Ok(self.input.get(self.pos).copied())
+    }
+
+    fn next(&mut self) -> Result<u8, String> {
+        let byte = self.peek()?.ok_or_else(|| self.malformed())?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    /// Skips whitespace, up to the next redacted byte.
+    fn skip_whitespace(&mut self) {
+        while !self.hidden()
+            && matches!(self.input.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips the redacted bytes at the current position together with the whitespace around
+    /// them, and returns whether there were any.
+    fn skip_hidden(&mut self) -> bool {
+        let start = self.pos;
+        while self.hidden() {
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+        self.pos > start
+    }
+
+    /// Resolves `tokens` from the value at the current position. The value found must be
+    /// disclosed whole.
+    fn find(&mut self, tokens: &[String]) -> Result<bool, String> {
+        let Some((token, tokens)) = tokens.split_first() else {
+            let start = self.pos;
+            self.skip_value()?;
+            let whole = !(start..self.pos).any(|pos| self.redacted.contains(pos));
+            return check(whole, || HIDDEN.to_string()).map(|()| true);
+        };
+
+        match self.peek()? {
+            Some(b'{') => {
+                let (members, hidden) = self.object()?;
+                match members.into_iter().rev().find(|(key, _)| key == token) {
+                    Some((_, Some(pos))) => {
+                        self.pos = pos;
+                        self.find(tokens)
+                    }
+                    Some((_, None)) => Err(HIDDEN.to_string()),
+                    None if hidden => Err(HIDDEN.to_string()),
+                    None => Ok(false),
+                }
+            }
+            Some(b'[') => {
+                let items = self.array()?;
+                let Ok(index) = token.parse::<usize>() else {
+                    return Ok(false);
+                };
+                let uncounted = items
+                    .iter()
+                    .take(index.saturating_add(1))
+                    .any(|item| *item == Item::Uncounted);
+                match items.get(index) {
+                    _ if uncounted => Err(HIDDEN.to_string()),
+                    Some(Item::Disclosed(pos)) => {
+                        self.pos = *pos;
+                        self.find(tokens)
+                    }
+                    Some(_) => Err(HIDDEN.to_string()),
+                    None => Ok(false),
+                }
+            }
+            _ => {
+                self.skip_value()?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Skips a value. Its redacted parts must be whole members or items, and a number or literal
+    /// must be followed by a disclosed byte or the end of the document.
+    fn skip_value(&mut self) -> Result<(), String> {
+        match self.peek()?.ok_or_else(|| self.malformed())? {
+            b'{' => {
+                self.object()?;
+            }
+            b'[' => {
+                self.array()?;
+            }
+            b'"' => {
+                self.string()?;
+            }
+            _ => {
+                let start = self.pos;
+                while !matches!(
+                    self.peek()?,
+                    None | Some(b',' | b']' | b'}' | b' ' | b'\t' | b'\n' | b'\r')
+                ) {
+                    self.pos += 1;
+                }
+                serde_json::from_slice::<serde::de::IgnoredAny>(&self.input[start..self.pos])
+                    .map_err(|_| self.malformed())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("response body is nested too deeply".to_string());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Reads an object. Returns its members with a disclosed key and whether some of it is
+    /// redacted.
+    fn object(&mut self) -> Result<(Vec<Member>, bool), String> {
+        self.enter()?;
+        let mut members = Vec::new();
+        let mut hidden = false;
+        loop {
+            self.skip_whitespace();
+            if self.skip_hidden() {
+                hidden = true;
+                continue;
+            }
+            match self.peek()?.ok_or_else(|| self.malformed())? {
+                b',' => self.pos += 1,
+                b'}' => break,
+                _ => {
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    if self.next()? != b':' {
+                        return Err(self.malformed());
+                    }
+                    self.skip_whitespace();
+                    if self.skip_hidden() {
+                        members.push((key, None));
+                        hidden = true;
+                        continue;
+                    }
+                    members.push((key, Some(self.pos)));
+                    self.skip_value()?;
+                }
+            }
+        }
+        self.pos += 1;
+        self.depth -= 1;
+        Ok((members, hidden))
+    }
+
+    /// Reads an array and returns its items.
+    fn array(&mut self) -> Result<Vec<Item>, String> {
+        self.enter()?;
+        let mut items = Vec::new();
+        // Whether the next item follows a separator
+        let mut separated = true;
+        loop {
+            self.skip_whitespace();
+            if self.skip_hidden() {
+                let between = separated && matches!(self.peek()?, Some(b',' | b']'));
+                items.push(if between {
+                    Item::Hidden
+                } else {
+                    Item::Uncounted
+                });
+                separated = false;
+                continue;
+            }
+            match self.peek()?.ok_or_else(|| self.malformed())? {
+                b',' => {
+                    self.pos += 1;
+                    separated = true;
+                }
+                b']' => break,
+                _ => {
+                    // An item without a separator before it follows a redacted part, which must
+                    // then hold the separator
+                    if !separated && items.last() != Some(&Item::Uncounted) {
+                        return Err(self.malformed());
+                    }
+                    items.push(Item::Disclosed(self.pos));
+                    self.skip_value()?;
+                    separated = false;
+                }
+            }
+        }
+        self.pos += 1;
+        self.depth -= 1;
+        Ok(items)
+    }
+
+    /// Reads a string and returns it unescaped.
+    fn string(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        if self.next()? != b'"' {
+            return Err(self.malformed());
+        }
+        loop {
+            match self.next()? {
+                b'\\' => {
+                    self.next()?;
+                }
+                b'"' => break,
+                _ => {}
+            }
+        }
+        serde_json::from_slice(&self.input[start..self.pos]).map_err(|_| self.malformed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disclose::{undisclosed_body_ranges, JsonSelector};
+    use chrono::TimeZone;
+
+    const SENT: &str = "GET /v1/accounts?page=2 HTTP/1.1\r\nHost: api.example.com\r\n\r\n";
+
+    /// Returns `data` with the first occurrence of every part of `hidden` redacted.
+    fn transcript(data: &str, hidden: &[&str]) -> VerifiedTranscript {
+        let redacted = hidden
+            .iter()
+            .map(|part| {
+                let start = data.find(part).expect("hidden parts are in the transcript");
+                start..start + part.len()
+            })
+            .collect();
+        redact(data, redacted)
+    }
+
+    fn redact(data: &str, redacted: RangeSet) -> VerifiedTranscript {
+        let mut data = data.as_bytes().to_vec();
+        for range in &redacted {
+            data[range.clone()].fill(0);
+        }
+        VerifiedTranscript { data, redacted }
+    }
+
+    fn response(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn presentation(sent: VerifiedTranscript, recv: VerifiedTranscript) -> VerifiedPresentation {
+        VerifiedPresentation {
+            server_name: "api.example.com".to_string(),
+            time: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            sent,
+            recv,
+            notary_key: p256::SecretKey::from_slice(&[1; 32]).unwrap().public_key(),
+        }
+    }
+
+    /// Evaluates `policy` `age_secs` after the session and returns the outcome of `rule`.
+    fn outcome(
+        policy: &VerificationPolicy,
+        presentation: &VerifiedPresentation,
+        age_secs: i64,
+        rule: &PolicyRule,
+    ) -> Result<(), String> {
+        let report = policy.evaluate_at(
+            presentation,
+            presentation.time + Duration::seconds(age_secs),
+        );
+        let result = report
+            .results
+            .iter()
+            .find(|result| result.rule == *rule)
+            .unwrap_or_else(|| panic!("{rule:?} was not evaluated"));
+        match &result.reason {
+            None if result.passed => Ok(()),
+            reason => Err(reason.clone().unwrap_or_default()),
+        }
+    }
+
+    #[test]
+    fn server_and_time_rules() {
+        let presentation = presentation(transcript(SENT, &[]), transcript(&response("{}"), &[]));
+        let servers = |servers: &[&str]| VerificationPolicy {
+            allowed_servers: servers.iter().map(ToString::to_string).collect(),
+            ..Default::default()
+        };
+        let max_age = VerificationPolicy {
+            max_age_secs: Some(300),
+            ..Default::default()
+        };
+        let default = VerificationPolicy::default();
+
+        let cases = [
+            (
+                servers(&["API.example.com"]),
+                0,
+                PolicyRule::AllowedServer,
+                true,
+            ),
+            (
+                servers(&["example.com"]),
+                0,
+                PolicyRule::AllowedServer,
+                false,
+            ),
+            (max_age.clone(), 300, PolicyRule::MaxAge, true),
+            (max_age, 301, PolicyRule::MaxAge, false),
+            (default.clone(), -60, PolicyRule::NotInFuture, true),
+            (default, -61, PolicyRule::NotInFuture, false),
+        ];
+        for (policy, age_secs, rule, passed) in &cases {
+            let outcome = outcome(policy, &presentation, *age_secs, rule);
+            assert_eq!(
+                outcome.is_ok(),
+                *passed,
+                "{rule:?} at {age_secs}s: {outcome:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn request_rules() {
+        let recv = transcript(&response("{}"), &[]);
+        let disclosed = presentation(transcript(SENT, &[]), recv.clone());
+        let hidden_method = presentation(transcript(SENT, &["GET"]), recv.clone());
+        let hidden_target = presentation(transcript(SENT, &["/v1/accounts?page=2"]), recv);
+        let method = |method: &str| VerificationPolicy {
+            method: Some(method.to_string()),
+            ..Default::default()
+        };
+        let path = |path: &str| VerificationPolicy {
+            path: Some(path.to_string()),
+            ..Default::default()
+        };
+
+        let cases = [
+            (method("get"), &disclosed, PolicyRule::Method, Ok(())),
+            (
+                method("POST"),
+                &disclosed,
+                PolicyRule::Method,
+                Err("request method is \"GET\""),
+            ),
+            (
+                method("GET"),
+                &hidden_method,
+                PolicyRule::Method,
+                Err("request method is redacted"),
+            ),
+            (path("/v1/accounts"), &disclosed, PolicyRule::Path, Ok(())),
+            (
+                path("/v1/accounts?page=2"),
+                &disclosed,
+                PolicyRule::Path,
+                Err("request path is \"/v1/accounts\""),
+            ),
+            (
+                path("/v1/accounts"),
+                &hidden_target,
+                PolicyRule::Path,
+                Err("request target is redacted"),
+            ),
+        ];
+        for (policy, presentation, rule, expected) in &cases {
+            assert_eq!(
+                outcome(policy, presentation, 0, rule),
+                expected.map_err(ToString::to_string),
+                "{policy:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn required_field_rule() {
+        const BODY: &str = r#"{"account":{"id":7,"balance":337},"tokens":["t1","t2"]}"#;
+        const CHUNKED: &str = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                               6\r\n{\"a\":1\r\n6\r\n,\"b\":2\r\n1\r\n}\r\n0\r\n\r\n";
+        const HIDDEN: Result<(), &str> = Err(super::HIDDEN);
+        const MISSING: Result<(), &str> = Err("field is missing");
+
+        // The response, the parts of it which are redacted, the pointer and the outcome
+        type Case = (
+            String,
+            &'static [&'static str],
+            &'static str,
+            Result<(), &'static str>,
+        );
+        let cases: &[Case] = &[
+            (response(BODY), &[], "", Ok(())),
+            (response(BODY), &[], "/account/balance", Ok(())),
+            (response(BODY), &[], "/tokens/1", Ok(())),
+            (response(BODY), &[], "/account/missing", MISSING),
+            (response(BODY), &[], "/tokens/2", MISSING),
+            (response(BODY), &[], "/tokens/x", MISSING),
+            (
+                response(BODY),
+                &[],
+                "account",
+                Err("invalid JSON pointer \"account\""),
+            ),
+            // Redacted members and items are skipped, but not redacted structure
+            (response(BODY), &["\"t2\""], "/account/balance", Ok(())),
+            (response(BODY), &["\"t2\""], "/tokens/0", Ok(())),
+            (response(BODY), &["\"t2\""], "/tokens/1", HIDDEN),
+            (response(BODY), &["\"id\":7,"], "/account/balance", Ok(())),
+            (response(BODY), &["7"], "/account/balance", Ok(())),
+            (response(BODY), &["337"], "/account/balance", HIDDEN),
+            (response(BODY), &["337"], "", HIDDEN),
+            (response(BODY), &["\"account\""], "/account/balance", HIDDEN),
+            (response(BODY), &["{\"id\""], "/account/balance", HIDDEN),
+            (response(BODY), &["\"id\":7"], "/account/id", HIDDEN),
+            // Items are counted by their separators
+            (
+                response(r#"{"a":["x","y","z"]}"#),
+                &["\"y\""],
+                "/a/2",
+                Ok(()),
+            ),
+            (
+                response(r#"{"a":["x","y","z"]}"#),
+                &["\"y\","],
+                "/a/2",
+                HIDDEN,
+            ),
+            (
+                response(r#"{"a":["x","y","z"]}"#),
+                &[",\"y\""],
+                "/a/2",
+                HIDDEN,
+            ),
+            // The last of repeated keys is the field
+            (response(r#"{"a":"x","a":"y"}"#), &["\"x\""], "/a", Ok(())),
+            (response(r#"{"a":"x","a":"y"}"#), &["\"y\""], "/a", HIDDEN),
+            // A member of the same name disclosed elsewhere in the body
+            (
+                response(r#"{"account":{"balance":337},"promo":{"balance":5}}"#),
+                &[r#"{"account":{"balance":337},"promo":{"#, "}}"],
+                "/account/balance",
+                HIDDEN,
+            ),
+            // A value truncated by a redaction
+            (
+                response(r#"{"balance": 1337}"#),
+                &["337"],
+                "/balance",
+                HIDDEN,
+            ),
+            // A value whose end cannot be told
+            (response(r#"{"balance":1}"#), &["}"], "/balance", HIDDEN),
+            (response(r#"{"a":"x"}"#), &["}"], "/a", HIDDEN),
+            (CHUNKED.to_string(), &["\"b\":2"], "/a", Ok(())),
+            (CHUNKED.to_string(), &["\"b\":2"], "/b", HIDDEN),
+            (
+                response("not json"),
+                &[],
+                "/a",
+                Err("response body is not valid JSON"),
+            ),
+        ];
+        for (recv, hidden, pointer, expected) in cases {
+            let presentation = presentation(transcript(SENT, &[]), transcript(recv, hidden));
+            let policy = VerificationPolicy {
+                required_fields: vec![pointer.to_string()],
+                ..Default::default()
+            };
+            let rule = PolicyRule::RequiredField(pointer.to_string());
+            assert_eq!(
+                outcome(&policy, &presentation, 0, &rule),
+                expected.map_err(ToString::to_string),
+                "{pointer:?} with {hidden:?} redacted"
+            );
+        }
+    }
+
+    #[test]
+    fn fields_selected_for_disclosure_are_located() {
+        let recv =
+            response(r#"{"account":{"id":7,"balance":337,"tags":["a","b"]},"tokens":["t1","t2"]}"#);
+        let pointer = |pointer: &str| JsonSelector::Pointer(pointer.to_string());
+
+        // The selectors, and the fields they disclose
+        let cases = [
+            (vec![pointer("/account/balance")], &["/account/balance"][..]),
+            (vec![pointer("/account/tags/1")], &["/account/tags/1"]),
+            (vec![pointer("/tokens/0")], &["/tokens/0"]),
+            (vec![pointer("/account")], &["/account", "/account/id"]),
+            (
+                vec![JsonSelector::Path("$.account.tags[*]".to_string())],
+                &["/account/tags/0", "/account/tags/1"],
+            ),
+        ];
+        for (selectors, fields) in cases {
+            let undisclosed = undisclosed_body_ranges(&selectors, recv.as_bytes()).unwrap();
+            let recv = redact(&recv, undisclosed);
+            for field in fields {
+                assert_eq!(field_disclosed(&recv, field), Ok(()), "{selectors:?}");
+            }
+            assert_eq!(
+                field_disclosed(&recv, "/tokens/1"),
+                Err(super::HIDDEN.to_string()),
+                "{selectors:?}"
+            );
+        }
+    }
+}
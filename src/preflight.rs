@@ -0,0 +1,188 @@
+use crate::{
+    http::{content_length, head_len, status_line},
+    redact::Direction,
+    trust::TrustStoreError,
+    NotarizationRequest, NotaryConfig, OpacityError, TrustStore,
+};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH},
+    ClientBuilder, Method, Response,
+};
+use tracing::{debug, warn};
+
+/// Bytes added to every preflight estimate, for headers which change between responses such as
+/// dates and cookies.
+const PREFLIGHT_MARGIN: usize = 512;
+
+/// Errors raised while sizing a session with a preflight request.
+#[derive(Debug, thiserror::Error)]
+enum PreflightError {
+    #[error("failed to load the server trust store: {0}")]
+    TrustStore(#[from] TrustStoreError),
+    #[error("invalid request header: {0}")]
+    Header(String),
+    #[error("preflight request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+/// Estimates the receive budget of a session for `request` with a plain, unnotarized request to
+/// the server.
+///
+/// The response is sized from the `Content-Length` of a `HEAD` request or, for `GET` requests
+/// answered without a usable one, by downloading it. Returns `None`, so that the configured
+/// budget is used, if the size cannot be found out.
+pub(crate) async fn preflight_recv_data(
+    request: &NotarizationRequest,
+    server_trust_store: &TrustStore,
+    config: &NotaryConfig,
+) -> Result<Option<usize>, OpacityError> {
+    match estimate_response_len(request, server_trust_store, config).await {
+        Ok(Some(needed)) => {
+            let budget = recv_budget(needed, config.max_recv_data_ceiling)?;
+            debug!("Preflight estimated a {needed} bytes response, receive budget is {budget}");
+            Ok(Some(budget))
+        }
+        Ok(None) => {
+            warn!("Preflight could not size the response, keeping the configured receive budget");
+            Ok(None)
+        }
+        Err(err) => {
+            warn!("{err}, keeping the configured receive budget");
+            Ok(None)
+        }
+    }
+}
+
+/// Returns the receive budget of a session expecting a response of `needed` bytes: the
+/// estimate with some headroom, capped by `ceiling`.
+///
+/// Fails with [`OpacityError::DataLimitExceeded`] if the estimate alone exceeds the ceiling,
+/// rather than running a session which cannot hold the response.
+fn recv_budget(needed: usize, ceiling: usize) -> Result<usize, OpacityError> {
+    if needed > ceiling {
+        return Err(OpacityError::DataLimitExceeded {
+            direction: Direction::Recv,
+            needed,
+            limit: ceiling,
+        });
+    }
+    Ok(needed
+        .saturating_add(needed / 4)
+        .saturating_add(PREFLIGHT_MARGIN)
+        .min(ceiling))
+}
+
+async fn estimate_response_len(
+    request: &NotarizationRequest,
+    server_trust_store: &TrustStore,
+    config: &NotaryConfig,
+) -> Result<Option<usize>, PreflightError> {
+    let builder = ClientBuilder::new()
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.request_timeout);
+    let client = server_trust_store.configure_reqwest(builder)?.build()?;
+
+    let url = format!("https://{}:{}{}", request.host, request.port, request.path);
+    let headers = preflight_headers(request)?;
+
+    let response = client
+        .head(&url)
+        .headers(headers.clone())
+        .send()
+        .await?
+        .error_for_status()?;
+    if let Some(content_length) = head_content_length(response.headers()) {
+        return Ok(Some(
+            response_head_len(&response).saturating_add(content_length),
+        ));
+    }
+
+    // Only a GET is safe to replay in full
+    if !request.method.eq_ignore_ascii_case(Method::GET.as_str()) {
+        return Ok(None);
+    }
+    let response = client
+        .get(&url)
+        .headers(headers)
+        .send()
+        .await?
+        .error_for_status()?;
+    let head = response_head_len(&response);
+    let body = response.bytes().await?;
+    Ok(Some(head.saturating_add(body.len())))
+}
+
+/// Returns the `Content-Length` of a `HEAD` response, unless it is 0, which some servers answer
+/// whatever the size of the `GET` response.
+fn head_content_length(headers: &HeaderMap) -> Option<usize> {
+    content_length(headers).filter(|&len| len > 0)
+}
+
+/// Returns the headers of `request`, minus those describing its body.
+fn preflight_headers(request: &NotarizationRequest) -> Result<HeaderMap, PreflightError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &request.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| PreflightError::Header(err.to_string()))?;
+        if name == CONTENT_LENGTH || name == reqwest::header::CONTENT_TYPE {
+            continue;
+        }
+        let value =
+            HeaderValue::from_str(value).map_err(|err| PreflightError::Header(err.to_string()))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// Lower bound of the number of bytes the head of `response` took on the wire.
+fn response_head_len(response: &Response) -> usize {
+    let status = response.status();
+    let status_line = status_line(status.as_str(), status.canonical_reason());
+    head_len(&status_line, response.headers())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budget_has_headroom_up_to_the_ceiling() {
+        assert_eq!(
+            recv_budget(1000, 1 << 16).unwrap(),
+            1000 + 250 + PREFLIGHT_MARGIN
+        );
+        assert_eq!(recv_budget(1000, 1200).unwrap(), 1200);
+        assert_eq!(recv_budget(1000, 1000).unwrap(), 1000);
+    }
+
+    #[test]
+    fn empty_head_responses_are_not_sized() {
+        let headers = |content_length: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(content_length) = content_length {
+                headers.insert(CONTENT_LENGTH, content_length.parse().unwrap());
+            }
+            headers
+        };
+
+        assert_eq!(head_content_length(&headers(Some("1337"))), Some(1337));
+        assert_eq!(head_content_length(&headers(Some("0"))), None);
+        assert_eq!(head_content_length(&headers(None)), None);
+    }
+
+    #[test]
+    fn estimate_beyond_the_ceiling_is_refused() {
+        let err = recv_budget(1001, 1000).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                OpacityError::DataLimitExceeded {
+                    direction: Direction::Recv,
+                    needed: 1001,
+                    limit: 1000,
+                }
+            ),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use crate::{
+    config::DataLimits,
+    disclose::undisclosed_body_ranges,
+    http::{content_length, head_len, status_line},
+    info::InfoClient,
+    preflight::preflight_recv_data,
+    range::RangeSet,
+    redact::Direction,
+    tls_prover,
+    verifier::signing_key,
+    NotarizationRequest, NotaryConfig, NotaryEndpoint, OpacityError, ProofEnvelope,
+    RecordedRedaction, TrustStore, TrustedNotaries,
+};
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::{Body, Bytes},
+    Request,
+};
+use hyper_util::rt::TokioIo;
+use std::{io, time::Duration};
+use tls_core::verify::WebPkiVerifier;
+use tlsn_core::proof::TlsProof;
+use tlsn_prover::tls::{state::Notarize, Prover, ProverConfig};
+use tokio::{net::TcpStream, task::JoinHandle};
+use tokio_util::compat::{FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+use tracing::{debug, warn};
+
+/// Options of a notarization session.
+#[derive(Debug, Clone)]
+pub struct NotarizeOptions {
+    /// Notaries to request the session from.
+    pub notary: NotaryConfig,
+    /// Root certificates the server is authenticated with, the `webpki-roots` set by default.
+    ///
+    /// This is independent of the notary's trust store in [`NotaryConfig`].
+    pub server_trust_store: TrustStore,
+}
+
+impl NotarizeOptions {
+    pub fn new(notary: NotaryConfig) -> Self {
+        Self {
+            notary,
+            server_trust_store: TrustStore::WebPki,
+        }
+    }
+
+    /// Authenticates the server with `trust_store`, e.g. the CA of an internal API or the
+    /// self-signed certificate of a local test server.
+    pub fn server_trust_store(mut self, trust_store: TrustStore) -> Self {
+        self.server_trust_store = trust_store;
+        self
+    }
+}
+
+/// Sends `request` to the server over an MPC-TLS connection with the notary and returns the
+/// resulting proof.
+///
+/// Every byte of both transcripts, including the request line, headers and body, is committed
+/// to and revealed, except for the ranges matched by the request's redaction rules and, when the
+/// request selects fields to disclose, the rest of the received body.
+///
+/// Failed attempts are retried after the notary config's [`RetryPolicy`](crate::RetryPolicy),
+/// each with a new notary session and thus a new session id. The notary session and server
+/// connection of a failed attempt are closed before the next one starts. Attempts which failed
+/// after the request was sent to the server are only retried for safe methods such as `GET`, see
+/// [`RetryPolicy::replay_backoff`](crate::RetryPolicy::replay_backoff).
+pub async fn notarize(
+    request: &NotarizationRequest,
+    options: &NotarizeOptions,
+) -> Result<TlsProof, OpacityError> {
+    Ok(notarize_with(request, options).await?.proof)
+}
+
+/// A proof together with how it was made.
+struct Notarized {
+    proof: TlsProof,
+    /// The notary which ran the session.
+    notary: NotaryEndpoint,
+    /// What each redaction rule of the request hid.
+    redactions: Vec<RecordedRedaction>,
+}
+
+/// Like [`notarize`], but also returns how the proof was made.
+async fn notarize_with(
+    request: &NotarizationRequest,
+    options: &NotarizeOptions,
+) -> Result<Notarized, OpacityError> {
+    let notary = &options.notary;
+    let mut limits = request.data_limits(notary.data_limits());
+    if notary.preflight && request.max_recv_data.is_none() {
+        if let Some(max_recv_data) =
+            preflight_recv_data(request, &options.server_trust_store, notary).await?
+        {
+            limits.max_recv_data = max_recv_data;
+        }
+    }
+
+    let mut attempt = 1;
+    loop {
+        let mut request_sent = false;
+        match notarize_once(request, options, limits, &mut request_sent).await {
+            Ok(notarized) => return Ok(notarized),
+            Err(err) => {
+                let delay = if request_sent {
+                    notary.retry.replay_backoff(attempt, &err, &request.method)
+                } else {
+                    notary.retry.backoff(attempt, &err)
+                };
+                let Some(delay) = delay else {
+                    return Err(err);
+                };
+                warn!("Notarization attempt {attempt} failed: {err}, retrying in {delay:?}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Runs a single notarization session.
+///
+/// `request_sent` is set once the request starts being written to the server. The tasks of the
+/// session are aborted on return, so that nothing is left running when it fails.
+async fn notarize_once(
+    request: &NotarizationRequest,
+    options: &NotarizeOptions,
+    limits: DataLimits,
+    request_sent: &mut bool,
+) -> Result<Notarized, OpacityError> {
+    let notary = &options.notary;
+    let http_request = request.to_http_request()?;
+    check_limit(
+        Direction::Sent,
+        request_len(&http_request),
+        limits.max_sent_data,
+    )?;
+
+    let server_root_cert_store = options
+        .server_trust_store
+        .server_root_cert_store()
+        .map_err(OpacityError::ServerTrustStore)?;
+
+    let session = tls_prover(notary, limits).await?;
+    debug!(
+        "Notary {} accepted session {}",
+        session.notary.address(),
+        session.session_id
+    );
+
+    let prover_config = ProverConfig::builder()
+        .id(session.session_id)
+        .server_dns(request.host.clone())
+        .root_cert_store(server_root_cert_store)
+        .max_sent_data(limits.max_sent_data)
+        .max_recv_data(limits.max_recv_data)
+        .build()
+        .map_err(|err| OpacityError::ProverConfig(err.to_string()))?;
+
+    debug!("Setting up prover");
+    let session_notary = notary.for_endpoint(&session.notary);
+    let (prover, ()) = tokio::join!(
+        Prover::new(prover_config).setup(session.connection.compat()),
+        check_notary_version(&session_notary),
+    );
+    let prover = prover?;
+
+    let client_socket = connect_server(&request.host, request.port, notary.connect_timeout).await?;
+
+    let (tls_connection, prover_fut) = prover.connect(client_socket.compat()).await?;
+    let ctrl = prover_fut.control();
+    let mut prover_task = AbortOnDrop(tokio::spawn(prover_fut));
+
+    let (mut request_sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(tls_connection.compat())).await?;
+
+    let _connection_task = AbortOnDrop(tokio::spawn(connection));
+
+    ctrl.defer_decryption().await?;
+
+    debug!("Sending request to server: {:?}", http_request);
+
+    *request_sent = true;
+    let response = request_sender.send_request(http_request).await?;
+
+    // Any 2xx, e.g. `201 Created` in answer to a POST, carries the response being proven
+    if !response.status().is_success() {
+        return Err(OpacityError::UnexpectedStatus(response.status()));
+    }
+
+    // Fail before the body is received if it cannot fit, rather than deep in the MPC
+    if let Some(content_length) = content_length(response.headers()) {
+        let status = response.status();
+        let status_line = status_line(status.as_str(), status.canonical_reason());
+        let needed = head_len(&status_line, response.headers()).saturating_add(content_length);
+        check_limit(Direction::Recv, needed, limits.max_recv_data)?;
+    }
+
+    let payload = response.into_body().collect().await?.to_bytes();
+    debug!(
+        "Received response from server: {:?}",
+        &String::from_utf8_lossy(&payload)
+    );
+
+    let prover = (&mut prover_task.0).await??.start_notarize();
+
+    let (proof, redactions) = build_proof(prover, request).await?;
+    Ok(Notarized {
+        proof,
+        notary: session.notary,
+        redactions,
+    })
+}
+
+/// Fetches the `/info` of the notary running the session, which logs a warning if the notary
+/// runs another tlsn version than this library.
+///
+/// This is best effort: the session goes on if `/info` cannot be fetched, e.g. without TLS.
+async fn check_notary_version(config: &NotaryConfig) {
+    if let Err(err) = InfoClient::shared().info(config).await {
+        debug!(
+            "Could not check the version of notary {}:{}: {err}",
+            config.host, config.port
+        );
+    }
+}
+
+fn check_limit(direction: Direction, needed: usize, limit: usize) -> Result<(), OpacityError> {
+    if needed > limit {
+        return Err(OpacityError::DataLimitExceeded {
+            direction,
+            needed,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// Number of bytes `request` takes on the wire, as written by hyper.
+fn request_len(request: &Request<Full<Bytes>>) -> usize {
+    let request_line = format!("{} {} HTTP/1.1", request.method(), request.uri());
+    let body = request.body().size_hint().exact().unwrap_or_default() as usize;
+    head_len(&request_line, request.headers()) + body
+}
+
+/// Like [`notarize`], but wraps the proof in a [`ProofEnvelope`] describing the session.
+///
+/// The envelope records the address of the notary which ran the session, and the fingerprint of
+/// its key if the session was signed by one of the keys pinned in the notary config.
+pub async fn notarize_envelope(
+    request: &NotarizationRequest,
+    options: &NotarizeOptions,
+) -> Result<ProofEnvelope, OpacityError> {
+    let notarized = notarize_with(request, options).await?;
+    let notary_key = signing_notary(&notarized.proof, &notarized.notary, options).await;
+    let mut envelope = ProofEnvelope::new(
+        notarized.proof,
+        request,
+        notarized.redactions,
+        notary_key.as_ref(),
+    );
+    envelope.metadata.notary = Some(notarized.notary.address());
+    Ok(envelope)
+}
+
+/// Returns the configured key of `notary` which signed `proof`, if any.
+async fn signing_notary(
+    proof: &TlsProof,
+    notary: &NotaryEndpoint,
+    options: &NotarizeOptions,
+) -> Option<p256::PublicKey> {
+    let trusted_notaries = TrustedNotaries::from_config(&options.notary.for_endpoint(notary))
+        .await
+        .ok()?;
+    let root_cert_store = options.server_trust_store.server_root_cert_store().ok()?;
+    let cert_verifier = WebPkiVerifier::new(root_cert_store, None);
+    signing_key(&proof.session, &trusted_notaries, &cert_verifier).ok()
+}
+
+/// A spawned task which is aborted when dropped, so that a session failing half way does not leave
+/// its MPC-TLS connection or HTTP connection running in the background.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Opens the TCP connection to the server which the MPC-TLS connection runs over.
+async fn connect_server(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<TcpStream, OpacityError> {
+    let server_error = |source: io::Error| OpacityError::ServerConnect {
+        host: host.to_string(),
+        port,
+        source,
+    };
+
+    tokio::time::timeout(timeout, TcpStream::connect((host, port)))
+        .await
+        .map_err(|elapsed| server_error(elapsed.into()))?
+        .map_err(server_error)
+}
+
+/// Commits to and reveals everything but the ranges hidden by `request`, then finalizes the
+/// session. Also returns what each redaction rule hid.
+async fn build_proof(
+    mut prover: Prover<Notarize>,
+    request: &NotarizationRequest,
+) -> Result<(TlsProof, Vec<RecordedRedaction>), OpacityError> {
+    let sent = prover.sent_transcript().data();
+    let recv = prover.recv_transcript().data();
+
+    let mut redactions = Vec::with_capacity(request.redactions.len());
+    for redaction in &request.redactions {
+        let transcript = match redaction.direction {
+            Direction::Sent => sent,
+            Direction::Recv => recv,
+        };
+        redactions.push(RecordedRedaction::new(
+            redaction,
+            redaction.resolve(transcript)?,
+        ));
+    }
+    let private_ranges = |direction: Direction| -> RangeSet {
+        redactions
+            .iter()
+            .filter(|redaction| redaction.direction == direction)
+            .flat_map(|redaction| redaction.ranges.iter().cloned())
+            .collect()
+    };
+
+    // Identify the ranges in the outbound data which contain data which we want to disclose
+    let sent_private_ranges = private_ranges(Direction::Sent);
+    let sent_public_ranges = sent_private_ranges.complement(0..sent.len());
+    // Identify the ranges in the inbound data which contain data which we want to disclose
+    let mut recv_private_ranges = private_ranges(Direction::Recv);
+    if !request.disclose.is_empty() {
+        recv_private_ranges =
+            recv_private_ranges.union(&undisclosed_body_ranges(&request.disclose, recv)?);
+    }
+    let recv_public_ranges = recv_private_ranges.complement(0..recv.len());
+
+    let builder = prover.commitment_builder();
+
+    // Commit to each range of the public outbound data which we want to disclose
+    let sent_commitments = sent_public_ranges
+        .iter()
+        .map(|range| builder.commit_sent(range))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| OpacityError::Proof(err.to_string()))?;
+    // Commit to each range of the public inbound data which we want to disclose
+    let recv_commitments = recv_public_ranges
+        .iter()
+        .map(|range| builder.commit_recv(range))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| OpacityError::Proof(err.to_string()))?;
+
+    // Finalize, returning the notarized session
+    let notarized_session = prover.finalize().await?;
+
+    // Create a proof for all committed data in this session
+    let mut proof_builder = notarized_session.data().build_substrings_proof();
+
+    // Reveal all the public ranges
+    for commitment_id in sent_commitments.into_iter().chain(recv_commitments) {
+        proof_builder
+            .reveal_by_id(commitment_id)
+            .map_err(|err| OpacityError::Proof(err.to_string()))?;
+    }
+
+    let substrings_proof = proof_builder
+        .build()
+        .map_err(|err| OpacityError::Proof(err.to_string()))?;
+
+    let proof = TlsProof {
+        session: notarized_session.session_proof(),
+        substrings: substrings_proof,
+    };
+    Ok((proof, redactions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn dropped_tasks_are_aborted() {
+        let (sender, receiver) = futures::channel::oneshot::channel::<()>();
+        let task = AbortOnDrop(tokio::spawn(async move {
+            let _sender = sender;
+            std::future::pending::<()>().await;
+        }));
+
+        drop(task);
+        // The sender is dropped together with the aborted task
+        assert!(receiver.await.is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::ops::Range;
+
+/// A set of byte offsets, stored as sorted, disjoint and non-adjacent ranges.
+///
+/// Redaction and disclosure are computed with this type so that overlapping, nested or empty
+/// ranges can never make a private byte end up in a public range.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct RangeSet {
+    ranges: Vec<Range<usize>>,
+}
+
+impl RangeSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds every offset of `range` to the set. Empty ranges are ignored.
+    pub fn insert(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+
+        // Ranges which overlap or touch `range` are merged into it.
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+
+        let mut merged = range;
+        if first < last {
+            merged.start = merged.start.min(self.ranges[first].start);
+            merged.end = merged.end.max(self.ranges[last - 1].end);
+        }
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    /// Returns the offsets in either set.
+    pub fn union(&self, other: &RangeSet) -> RangeSet {
+        let mut union = self.clone();
+        union.extend(other.iter().cloned());
+        union
+    }
+
+    /// Returns the offsets in both sets.
+    pub fn intersection(&self, other: &RangeSet) -> RangeSet {
+        let mut intersection = RangeSet::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            intersection.insert(a.start.max(b.start)..a.end.min(b.end));
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        intersection
+    }
+
+    /// Returns the offsets of `within` which are not in the set.
+    pub fn complement(&self, within: Range<usize>) -> RangeSet {
+        let mut complement = RangeSet::new();
+        let mut start = within.start;
+        for range in &self.ranges {
+            if range.start >= within.end {
+                break;
+            }
+            complement.insert(start..range.start.min(within.end));
+            start = start.max(range.end);
+        }
+        complement.insert(start..within.end);
+        complement
+    }
+
+    /// Returns the offsets in the set but not in `other`.
+    pub fn difference(&self, other: &RangeSet) -> RangeSet {
+        match (self.ranges.first(), self.ranges.last()) {
+            (Some(first), Some(last)) => {
+                self.intersection(&other.complement(first.start..last.end))
+            }
+            _ => RangeSet::new(),
+        }
+    }
+
+    /// Whether `offset` is in the set.
+    pub fn contains(&self, offset: usize) -> bool {
+        let idx = self.ranges.partition_point(|r| r.end <= offset);
+        self.ranges
+            .get(idx)
+            .is_some_and(|range| range.contains(&offset))
+    }
+
+    /// Number of offsets in the set.
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|range| range.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Iterates over the ranges of the set, in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Range<usize>> {
+        self.ranges.iter()
+    }
+
+    /// Returns the ranges of the set, in order.
+    pub fn into_ranges(self) -> Vec<Range<usize>> {
+        self.ranges
+    }
+}
+
+impl Extend<Range<usize>> for RangeSet {
+    fn extend<T: IntoIterator<Item = Range<usize>>>(&mut self, iter: T) {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+impl FromIterator<Range<usize>> for RangeSet {
+    fn from_iter<T: IntoIterator<Item = Range<usize>>>(iter: T) -> Self {
+        let mut set = RangeSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a> IntoIterator for &'a RangeSet {
+    type Item = &'a Range<usize>;
+    type IntoIter = std::slice::Iter<'a, Range<usize>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
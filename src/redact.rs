@@ -0,0 +1,184 @@
+use crate::{
+    http::{HttpMessage, HttpParseError},
+    json::{JsonError, JsonValue},
+};
+use regex::bytes::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Errors raised while resolving a [`Redaction`] against a transcript.
+#[derive(Debug, thiserror::Error)]
+pub enum RedactionError {
+    #[error("literal redactions must not be empty")]
+    EmptyLiteral,
+    #[error("invalid redaction regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
+    #[error("cannot parse the {direction} transcript as HTTP: {source}")]
+    Http {
+        direction: Direction,
+        source: HttpParseError,
+    },
+    #[error("cannot apply JSON pointer {pointer:?} to the {direction} body: {source}")]
+    Json {
+        pointer: String,
+        direction: Direction,
+        source: JsonError,
+    },
+}
+
+/// Side of the TLS connection a rule applies to.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Data sent by the prover to the server.
+    Sent,
+    /// Data received by the prover from the server.
+    Recv,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Sent => write!(f, "sent"),
+            Direction::Recv => write!(f, "received"),
+        }
+    }
+}
+
+/// What a [`Redaction`] hides.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionMatch {
+    /// Every occurrence of the given string.
+    Literal(String),
+    /// Every match of the given regular expression.
+    Regex(String),
+    /// The value of every header with the given name, compared case-insensitively.
+    Header(String),
+    /// The value found at the given JSON pointer (RFC 6901) in the body, once any chunk framing
+    /// is removed.
+    JsonPointer(String),
+    /// A whole part of the HTTP message.
+    Part(HttpPart),
+}
+
+/// Kind of a [`RedactionMatch`], without the value it matches.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionKind {
+    Literal,
+    Regex,
+    Header,
+    JsonPointer,
+    Part,
+}
+
+impl RedactionMatch {
+    pub fn kind(&self) -> RedactionKind {
+        match self {
+            RedactionMatch::Literal(_) => RedactionKind::Literal,
+            RedactionMatch::Regex(_) => RedactionKind::Regex,
+            RedactionMatch::Header(_) => RedactionKind::Header,
+            RedactionMatch::JsonPointer(_) => RedactionKind::JsonPointer,
+            RedactionMatch::Part(_) => RedactionKind::Part,
+        }
+    }
+}
+
+/// A part of an HTTP message.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpPart {
+    /// The request line or the status line.
+    StartLine,
+    /// Every header line, names included.
+    Headers,
+    /// The body.
+    Body,
+}
+
+/// A rule hiding part of a transcript from the proof.
+///
+/// In JSON a rule reads as `{"direction": "sent", "header": "User-Agent"}`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct Redaction {
+    pub direction: Direction,
+    #[serde(flatten)]
+    pub target: RedactionMatch,
+}
+
+impl Redaction {
+    pub fn new(direction: Direction, target: RedactionMatch) -> Self {
+        Self { direction, target }
+    }
+
+    /// Returns the ranges of `transcript` hidden by this rule.
+    ///
+    /// `transcript` must be the transcript of the rule's direction.
+    pub fn resolve(&self, transcript: &[u8]) -> Result<Vec<Range<usize>>, RedactionError> {
+        match &self.target {
+            RedactionMatch::Literal(literal) => {
+                if literal.is_empty() {
+                    return Err(RedactionError::EmptyLiteral);
+                }
+                let literal = literal.as_bytes();
+                Ok(transcript
+                    .windows(literal.len())
+                    .enumerate()
+                    .filter(|(_, window)| *window == literal)
+                    .map(|(idx, _)| idx..idx + literal.len())
+                    .collect())
+            }
+            RedactionMatch::Regex(pattern) => Ok(Regex::new(pattern)?
+                .find_iter(transcript)
+                .map(|m| m.range())
+                .collect()),
+            RedactionMatch::Header(name) => {
+                let message = self.parse_message(transcript)?;
+                Ok(message
+                    .headers_named(transcript, name)
+                    .map(|header| header.value.clone())
+                    .filter(|value| !value.is_empty())
+                    .collect())
+            }
+            RedactionMatch::JsonPointer(pointer) => {
+                let json_error = |source: JsonError| RedactionError::Json {
+                    pointer: pointer.clone(),
+                    direction: self.direction,
+                    source,
+                };
+                let message = self.parse_message(transcript)?;
+                let body =
+                    JsonValue::parse(&message.decoded_body(transcript)).map_err(json_error)?;
+                let value = body.pointer(pointer).map_err(json_error)?;
+                Ok(value
+                    .map(|value| message.transcript_ranges(value.range.clone()))
+                    .unwrap_or_default())
+            }
+            RedactionMatch::Part(part) => {
+                let message = self.parse_message(transcript)?;
+                let range = match part {
+                    HttpPart::StartLine => message.start_line,
+                    HttpPart::Headers => match (message.headers.first(), message.headers.last()) {
+                        (Some(first), Some(last)) => first.line.start..last.line.end,
+                        _ => return Ok(Vec::new()),
+                    },
+                    HttpPart::Body => message.body,
+                };
+                Ok([range].into_iter().filter(|r| !r.is_empty()).collect())
+            }
+        }
+    }
+
+    fn parse_message(&self, transcript: &[u8]) -> Result<HttpMessage, RedactionError> {
+        match self.direction {
+            Direction::Sent => HttpMessage::parse_request(transcript),
+            Direction::Recv => HttpMessage::parse_response(transcript),
+        }
+        .map_err(|source| RedactionError::Http {
+            direction: self.direction,
+            source,
+        })
+    }
+}
@@ -0,0 +1,243 @@
+use http_body_util::Full;
+use hyper::{
+    body::Bytes,
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    Method, Request,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::DataLimits, disclose::JsonSelector, redact::Redaction};
+
+/// Description of the HTTP request to send to the server and notarize.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+pub struct NotarizationRequest {
+    /// Host name of the server, also used as the TLS server name.
+    pub host: String,
+    /// Port of the server, 443 by default.
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// HTTP method of the request, `GET` by default.
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Path and query of the request.
+    pub path: String,
+    /// Headers sent with the request, in order.
+    pub headers: Vec<(String, String)>,
+    /// Body sent with the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<RequestBody>,
+    /// Rules hiding parts of the transcripts from the proof.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redactions: Vec<Redaction>,
+    /// Parts of the received JSON body to disclose.
+    ///
+    /// When empty the whole received transcript is disclosed, except for redactions. Otherwise
+    /// only the selected keys and values of the body, and the brackets and keys leading to them,
+    /// are disclosed on top of the response head.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disclose: Vec<JsonSelector>,
+    /// Maximum number of bytes to send, overriding the notary config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_sent_data: Option<usize>,
+    /// Maximum number of bytes to receive, overriding the notary config.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_recv_data: Option<usize>,
+}
+
+/// Body of a [`NotarizationRequest`].
+///
+/// The body is part of the sent transcript, so it is committed to like the rest of the request.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestBody {
+    /// A JSON document, sent as `application/json`.
+    Json(serde_json::Value),
+    /// Form fields, sent as `application/x-www-form-urlencoded`.
+    Form(Vec<(String, String)>),
+    /// Raw text, sent without a default content type.
+    Text(String),
+}
+
+impl RequestBody {
+    /// Returns the encoded body together with its default content type.
+    fn encode(&self) -> (Bytes, Option<&'static str>) {
+        match self {
+            RequestBody::Json(value) => (Bytes::from(value.to_string()), Some("application/json")),
+            RequestBody::Form(fields) => {
+                let encoded = url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(fields)
+                    .finish();
+                (
+                    Bytes::from(encoded),
+                    Some("application/x-www-form-urlencoded"),
+                )
+            }
+            RequestBody::Text(text) => (Bytes::from(text.clone()), None),
+        }
+    }
+}
+
+fn default_method() -> String {
+    Method::GET.to_string()
+}
+
+fn default_port() -> u16 {
+    443
+}
+
+impl NotarizationRequest {
+    /// Returns the data limits of the session, falling back to `defaults` for those the request
+    /// does not set.
+    pub fn data_limits(&self, defaults: DataLimits) -> DataLimits {
+        DataLimits {
+            max_sent_data: self.max_sent_data.unwrap_or(defaults.max_sent_data),
+            max_recv_data: self.max_recv_data.unwrap_or(defaults.max_recv_data),
+        }
+    }
+
+    /// Builds the hyper request sent over the MPC-TLS connection.
+    ///
+    /// `Content-Type` and `Content-Length` are added for the body unless they are already part
+    /// of the headers.
+    pub(crate) fn to_http_request(&self) -> Result<Request<Full<Bytes>>, hyper::http::Error> {
+        let method = Method::from_bytes(self.method.to_ascii_uppercase().as_bytes())?;
+        let mut builder = Request::builder().method(method).uri(self.path.as_str());
+
+        for (header_name, header_value) in &self.headers {
+            builder = builder.header(header_name.as_str(), header_value.as_str());
+        }
+
+        let Some(body) = &self.body else {
+            return builder.body(Full::new(Bytes::new()));
+        };
+
+        let (body, content_type) = body.encode();
+        if let Some(content_type) = content_type {
+            if !self.has_header(CONTENT_TYPE.as_str()) {
+                builder = builder.header(CONTENT_TYPE, content_type);
+            }
+        }
+        if !self.has_header(CONTENT_LENGTH.as_str()) {
+            builder = builder.header(CONTENT_LENGTH, body.len());
+        }
+
+        builder.body(Full::new(body))
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper_util::rt::TokioIo;
+    use tokio::io::AsyncReadExt;
+
+    /// Returns the bytes hyper writes for `request`, i.e. its sent transcript.
+    async fn sent_transcript(request: &NotarizationRequest) -> String {
+        let (client, mut server) = tokio::io::duplex(1 << 16);
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(client))
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+        let http_request = request.to_http_request().unwrap();
+        tokio::spawn(async move { sender.send_request(http_request).await });
+
+        let mut sent = Vec::new();
+        while !is_complete(&sent) {
+            let mut buf = [0; 1024];
+            let n = server.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed before the request was sent");
+            sent.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8(sent).unwrap()
+    }
+
+    /// Whether `sent` holds a whole request head, and as much body as its `content-length` says.
+    fn is_complete(sent: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(sent);
+        let Some((head, body)) = text.split_once("\r\n\r\n") else {
+            return false;
+        };
+        let content_length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .map_or(0, |len| len.parse().unwrap());
+        body.len() >= content_length
+    }
+
+    fn request(body: Option<RequestBody>) -> NotarizationRequest {
+        let mut request: NotarizationRequest = serde_json::from_value(serde_json::json!({
+            "host": "api.example.com",
+            "method": "post",
+            "path": "/v1/transfers",
+            "headers": [["Host", "api.example.com"]],
+        }))
+        .unwrap();
+        request.body = body;
+        request
+    }
+
+    #[tokio::test]
+    async fn json_body_is_sent_with_its_headers() {
+        let body = RequestBody::Json(serde_json::json!({"amount": 10}));
+        let sent = sent_transcript(&request(Some(body))).await;
+
+        assert!(
+            sent.starts_with("POST /v1/transfers HTTP/1.1\r\n"),
+            "{sent}"
+        );
+        assert!(
+            sent.contains("\r\ncontent-type: application/json\r\n"),
+            "{sent}"
+        );
+        assert!(sent.contains("\r\ncontent-length: 13\r\n"), "{sent}");
+        assert!(sent.ends_with("\r\n\r\n{\"amount\":10}"), "{sent}");
+    }
+
+    #[tokio::test]
+    async fn form_body_is_url_encoded() {
+        let body = RequestBody::Form(vec![
+            ("to".to_string(), "a b".to_string()),
+            ("amount".to_string(), "10".to_string()),
+        ]);
+        let sent = sent_transcript(&request(Some(body))).await;
+
+        assert!(
+            sent.contains("\r\ncontent-type: application/x-www-form-urlencoded\r\n"),
+            "{sent}"
+        );
+        assert!(sent.contains("\r\ncontent-length: 16\r\n"), "{sent}");
+        assert!(sent.ends_with("\r\n\r\nto=a+b&amount=10"), "{sent}");
+    }
+
+    #[tokio::test]
+    async fn explicit_headers_are_not_duplicated() {
+        let mut request = request(Some(RequestBody::Text("hello".to_string())));
+        request
+            .headers
+            .push(("Content-Type".to_string(), "text/plain".to_string()));
+        let sent = sent_transcript(&request).await;
+
+        assert_eq!(sent.matches("content-type").count(), 1, "{sent}");
+        assert!(sent.contains("\r\ncontent-type: text/plain\r\n"), "{sent}");
+        assert!(sent.contains("\r\ncontent-length: 5\r\n"), "{sent}");
+        assert!(sent.ends_with("\r\n\r\nhello"), "{sent}");
+    }
+
+    #[tokio::test]
+    async fn request_without_body_has_no_body_headers() {
+        let mut request = request(None);
+        request.method = "GET".to_string();
+        let sent = sent_transcript(&request).await;
+
+        assert!(sent.starts_with("GET /v1/transfers HTTP/1.1\r\n"), "{sent}");
+        assert!(!sent.contains("content-type"), "{sent}");
+        assert!(sent.ends_with("\r\n\r\n"), "{sent}");
+    }
+}
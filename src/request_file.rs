@@ -0,0 +1,224 @@
+use crate::NotarizationRequest;
+use schemars::schema::RootSchema;
+use serde::Deserialize;
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+/// Errors raised while reading notarization requests from a document.
+#[derive(Debug, thiserror::Error)]
+pub enum RequestFileError {
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("failed to read requests from stdin: {0}")]
+    Stdin(io::Error),
+    #[error("invalid JSON request document: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid YAML request document: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("the document holds no request")]
+    Empty,
+}
+
+/// Encoding of a request document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestFormat {
+    Json,
+    Yaml,
+}
+
+impl RequestFormat {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(RequestFormat::Json),
+            "yaml" | "yml" => Some(RequestFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format of a document from its first character.
+    fn sniff(input: &str) -> Self {
+        match input.trim_start().chars().next() {
+            Some('{' | '[') => RequestFormat::Json,
+            _ => RequestFormat::Yaml,
+        }
+    }
+}
+
+impl NotarizationRequest {
+    /// Reads every request of a JSON or YAML file, or of stdin if `path` is `-`.
+    ///
+    /// The format is taken from the file extension and otherwise guessed from the content.
+    pub fn load_all(path: impl AsRef<Path>) -> Result<Vec<Self>, RequestFileError> {
+        let path = path.as_ref();
+        let input = if path == Path::new("-") {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(RequestFileError::Stdin)?;
+            input
+        } else {
+            std::fs::read_to_string(path).map_err(|source| RequestFileError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?
+        };
+        Self::parse_all(&input, RequestFormat::from_path(path))
+    }
+
+    /// Parses every request of a document.
+    ///
+    /// A JSON document holds one request or an array of them. A YAML document may also be a
+    /// stream of several documents separated by `---`. The format is guessed if not given.
+    pub fn parse_all(
+        input: &str,
+        format: Option<RequestFormat>,
+    ) -> Result<Vec<Self>, RequestFileError> {
+        let mut requests = Vec::new();
+        match format.unwrap_or_else(|| RequestFormat::sniff(input)) {
+            // Lists are told apart up front so that errors point into the right type
+            RequestFormat::Json => match serde_json::from_str(input)? {
+                serde_json::Value::Array(list) => {
+                    for request in list {
+                        requests.push(serde_json::from_value(request)?);
+                    }
+                }
+                request => requests.push(serde_json::from_value(request)?),
+            },
+            RequestFormat::Yaml => {
+                for document in serde_yaml::Deserializer::from_str(input) {
+                    match serde_yaml::Value::deserialize(document)? {
+                        serde_yaml::Value::Sequence(list) => {
+                            for request in list {
+                                requests.push(serde_yaml::from_value(request)?);
+                            }
+                        }
+                        serde_yaml::Value::Null => {}
+                        request => requests.push(serde_yaml::from_value(request)?),
+                    }
+                }
+            }
+        }
+
+        if requests.is_empty() {
+            return Err(RequestFileError::Empty);
+        }
+        Ok(requests)
+    }
+
+    /// Returns the JSON Schema of a single request, as published in
+    /// `schema/notarization-request.schema.json`.
+    pub fn json_schema() -> RootSchema {
+        schemars::schema_for!(NotarizationRequest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts(requests: &[NotarizationRequest]) -> Vec<&str> {
+        requests
+            .iter()
+            .map(|request| request.host.as_str())
+            .collect()
+    }
+
+    fn parse(input: &str, format: Option<RequestFormat>) -> Vec<NotarizationRequest> {
+        NotarizationRequest::parse_all(input, format).unwrap()
+    }
+
+    #[test]
+    fn json_holds_one_request_or_a_list() {
+        let single = r#"{"host": "a.example", "path": "/", "headers": []}"#;
+        assert_eq!(
+            hosts(&parse(single, Some(RequestFormat::Json))),
+            ["a.example"]
+        );
+
+        let list = r#"[
+            {"host": "a.example", "path": "/", "headers": []},
+            {"host": "b.example", "path": "/", "headers": [["Accept", "*/*"]], "method": "POST"}
+        ]"#;
+        let requests = parse(list, Some(RequestFormat::Json));
+        assert_eq!(hosts(&requests), ["a.example", "b.example"]);
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(
+            requests[1].headers,
+            [("Accept".to_string(), "*/*".to_string())]
+        );
+    }
+
+    #[test]
+    fn yaml_streams_skip_empty_documents() {
+        let stream = "\
+---
+host: a.example
+path: /
+headers: []
+---
+---
+~
+---
+- host: b.example
+  path: /
+  headers: []
+- host: c.example
+  path: /
+  headers: []
+";
+        assert_eq!(
+            hosts(&parse(stream, Some(RequestFormat::Yaml))),
+            ["a.example", "b.example", "c.example"]
+        );
+    }
+
+    #[test]
+    fn format_is_sniffed_without_an_extension() {
+        assert_eq!(RequestFormat::from_path(Path::new("-")), None);
+        assert_eq!(RequestFormat::from_path(Path::new("requests")), None);
+        assert_eq!(
+            RequestFormat::from_path(Path::new("requests.yml")),
+            Some(RequestFormat::Yaml)
+        );
+
+        let json = r#"  [{"host": "a.example", "path": "/", "headers": []}]"#;
+        let yaml = "host: b.example\npath: /\nheaders: []\n";
+        assert_eq!(RequestFormat::sniff(json), RequestFormat::Json);
+        assert_eq!(RequestFormat::sniff(yaml), RequestFormat::Yaml);
+        assert_eq!(hosts(&parse(json, None)), ["a.example"]);
+        assert_eq!(hosts(&parse(yaml, None)), ["b.example"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("requests");
+        std::fs::write(&path, json).unwrap();
+        let requests = NotarizationRequest::load_all(&path).unwrap();
+        assert_eq!(hosts(&requests), ["a.example"]);
+    }
+
+    #[test]
+    fn documents_without_requests_are_refused() {
+        let cases = [
+            ("", None),
+            ("  \n", None),
+            ("---\n---\n", None),
+            ("[]", Some(RequestFormat::Json)),
+            ("[]", Some(RequestFormat::Yaml)),
+        ];
+        for (input, format) in cases {
+            assert!(
+                matches!(
+                    NotarizationRequest::parse_all(input, format),
+                    Err(RequestFileError::Empty)
+                ),
+                "{input:?} as {format:?}"
+            );
+        }
+        assert!(matches!(
+            NotarizationRequest::parse_all("{", Some(RequestFormat::Json)),
+            Err(RequestFileError::Json(_))
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::OpacityError;
+use rand::Rng;
+use std::{fmt, str::FromStr, time::Duration};
+
+/// Default number of attempts of a notarization.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// Default delay before the second attempt of a notarization.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+/// Default upper bound of the delay between two attempts.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// Default share of each delay which is randomized, in percent.
+pub const DEFAULT_JITTER_PERCENT: u32 = 50;
+
+/// A kind of failure a notarization may be retried after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryClass {
+    /// No notary could be reached, or none accepted the session in time.
+    Notary,
+    /// The TCP connection to the server could not be opened.
+    Server,
+    /// The MPC-TLS session failed once started, e.g. during the MPC setup or on a dropped
+    /// connection.
+    Session,
+    /// The server answered with a 5xx or 429 status.
+    ServerStatus,
+}
+
+impl RetryClass {
+    /// Classes retried by default: every failure which is usually transient, but not error
+    /// statuses, which may not be safe to replay.
+    pub const DEFAULT: &'static [RetryClass] =
+        &[RetryClass::Notary, RetryClass::Server, RetryClass::Session];
+
+    /// Returns the class of `err`, or `None` if retrying cannot help.
+    pub fn of(err: &OpacityError) -> Option<Self> {
+        match err {
+            OpacityError::ConnectionRefused { .. } | OpacityError::Timeout { .. } => {
+                Some(RetryClass::Notary)
+            }
+            // Only worth retrying if every notary failed in a retryable way
+            OpacityError::AllNotariesFailed(errors) => errors
+                .iter()
+                .all(|err| RetryClass::of(err) == Some(RetryClass::Notary))
+                .then_some(RetryClass::Notary),
+            OpacityError::ServerConnect { .. } => Some(RetryClass::Server),
+            OpacityError::Prover(_) | OpacityError::ProverTask(_) | OpacityError::Http(_) => {
+                Some(RetryClass::Session)
+            }
+            OpacityError::UnexpectedStatus(status)
+                if status.is_server_error() || status.as_u16() == 429 =>
+            {
+                Some(RetryClass::ServerStatus)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for RetryClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "notary" => Ok(RetryClass::Notary),
+            "server" => Ok(RetryClass::Server),
+            "session" => Ok(RetryClass::Session),
+            "server_status" => Ok(RetryClass::ServerStatus),
+            _ => Err(format!(
+                "unknown retry class {s:?}, expected `notary`, `server`, `session` or \
+                 `server_status`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RetryClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryClass::Notary => write!(f, "notary"),
+            RetryClass::Server => write!(f, "server"),
+            RetryClass::Session => write!(f, "session"),
+            RetryClass::ServerStatus => write!(f, "server_status"),
+        }
+    }
+}
+
+/// How a failed notarization is retried.
+///
+/// Every attempt runs the whole flow again, from requesting a new session from the notaries to
+/// building the proof. Attempts are separated by an exponential backoff: the delay doubles after
+/// each attempt up to `max_backoff`, and a random share of it, up to `jitter_percent`, is taken
+/// off so that provers failing together do not retry together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of attempts, the first one included. 1 disables retries.
+    pub max_attempts: u32,
+    /// Delay before the second attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound of the delay between two attempts.
+    pub max_backoff: Duration,
+    /// Share of each delay which is randomized, in percent.
+    pub jitter_percent: u32,
+    /// Kinds of failure which are retried.
+    pub retry_on: Vec<RetryClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            jitter_percent: DEFAULT_JITTER_PERCENT,
+            retry_on: RetryClass::DEFAULT.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which never retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Returns how long to wait before another attempt after `attempt`, counted from 1, failed
+    /// with `err`, or `None` if the notarization must fail.
+    pub fn backoff(&self, attempt: u32, err: &OpacityError) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let class = RetryClass::of(err)?;
+        if !self.retry_on.contains(&class) {
+            return None;
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let jitter = f64::from(self.jitter_percent.min(100)) / 100.0;
+        Some(delay.mul_f64(1.0 - jitter * rand::thread_rng().gen::<f64>()))
+    }
+    /// Like [`RetryPolicy::backoff`], for an attempt which failed after its request was sent to
+    /// the server.
+    ///
+    /// The server may have acted on the request, so it is only sent again if `method` is safe
+    /// (RFC 9110): `GET`, `HEAD`, `OPTIONS` or `TRACE`.
+    pub fn replay_backoff(
+        &self,
+        attempt: u32,
+        err: &OpacityError,
+        method: &str,
+    ) -> Option<Duration> {
+        let safe = ["GET", "HEAD", "OPTIONS", "TRACE"]
+            .iter()
+            .any(|safe| method.eq_ignore_ascii_case(safe));
+        if !safe {
+            return None;
+        }
+        self.backoff(attempt, err)
+    }
+}
@@ -0,0 +1,187 @@
+use rustls::{Certificate, OwnedTrustAnchor, RootCertStore};
+use std::{io, path::PathBuf};
+use tls_core::{anchors, key};
+
+/// Errors raised while building a root certificate store from a [`TrustStore`].
+#[derive(Debug, thiserror::Error)]
+pub enum TrustStoreError {
+    #[error("failed to read CA bundle {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error("failed to parse PEM certificates: {0}")]
+    Pem(io::Error),
+    #[error("failed to load the system trust store: {0}")]
+    System(io::Error),
+    #[error("no certificates found in trust store")]
+    Empty,
+    #[error("invalid certificate #{index} in trust store: {reason}")]
+    InvalidCertificate { index: usize, reason: String },
+}
+
+/// Set of root certificates used to authenticate a TLS peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustStore {
+    /// Every certificate of a PEM bundle on disk.
+    PemFile(PathBuf),
+    /// Every certificate of a PEM bundle held in memory.
+    Pem(Vec<u8>),
+    /// The Mozilla root certificates shipped with the `webpki-roots` crate.
+    WebPki,
+    /// The root certificates of the operating system.
+    System,
+}
+
+impl TrustStore {
+    /// Builds the root certificate store described by this trust store.
+    pub fn root_cert_store(&self) -> Result<RootCertStore, TrustStoreError> {
+        self.build_root_store()
+    }
+
+    /// Builds the same store for the `tls-core` fork of rustls, which authenticates the server of
+    /// a notarized session, both in the prover and when verifying a proof.
+    pub fn server_root_cert_store(&self) -> Result<anchors::RootCertStore, TrustStoreError> {
+        self.build_root_store()
+    }
+
+    fn build_root_store<S: RootStore>(&self) -> Result<S, TrustStoreError> {
+        let mut root_cert_store = S::empty();
+
+        match self {
+            TrustStore::PemFile(_) | TrustStore::Pem(_) => {
+                let certificates = self.custom_certificates()?.unwrap_or_default();
+                for (index, certificate) in certificates.into_iter().enumerate() {
+                    root_cert_store
+                        .add_der(certificate)
+                        .map_err(|reason| TrustStoreError::InvalidCertificate { index, reason })?;
+                }
+            }
+            TrustStore::WebPki => root_cert_store.add_webpki_roots(),
+            TrustStore::System => {
+                let certificates: Vec<Vec<u8>> = rustls_native_certs::load_native_certs()
+                    .map_err(TrustStoreError::System)?
+                    .into_iter()
+                    .map(|certificate| certificate.0)
+                    .collect();
+                root_cert_store.add_parsable(&certificates);
+            }
+        }
+
+        if root_cert_store.is_empty() {
+            return Err(TrustStoreError::Empty);
+        }
+
+        Ok(root_cert_store)
+    }
+
+    /// Makes a `reqwest` client trust this store instead of its built-in roots.
+    pub(crate) fn configure_reqwest(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, TrustStoreError> {
+        if let Some(certificates) = self.custom_certificates()? {
+            builder = builder.tls_built_in_root_certs(false);
+            for (index, certificate) in certificates.iter().enumerate() {
+                let certificate = reqwest::Certificate::from_der(certificate).map_err(|err| {
+                    TrustStoreError::InvalidCertificate {
+                        index,
+                        reason: err.to_string(),
+                    }
+                })?;
+                builder = builder.add_root_certificate(certificate);
+            }
+        } else if *self == TrustStore::System {
+            builder = builder.tls_built_in_root_certs(true);
+        }
+        Ok(builder)
+    }
+
+    /// Returns the DER certificates of a PEM trust store, or `None` for the built-in root sets.
+    pub(crate) fn custom_certificates(&self) -> Result<Option<Vec<Vec<u8>>>, TrustStoreError> {
+        let pem = match self {
+            TrustStore::PemFile(path) => {
+                std::fs::read(path).map_err(|source| TrustStoreError::Io {
+                    path: path.clone(),
+                    source,
+                })?
+            }
+            TrustStore::Pem(pem) => pem.clone(),
+            TrustStore::WebPki | TrustStore::System => return Ok(None),
+        };
+
+        let certificates =
+            rustls_pemfile::certs(&mut pem.as_slice()).map_err(TrustStoreError::Pem)?;
+        if certificates.is_empty() {
+            return Err(TrustStoreError::Empty);
+        }
+        Ok(Some(certificates))
+    }
+}
+
+/// A root certificate store of rustls or of its `tls-core` fork, which share their API but not
+/// their types.
+trait RootStore {
+    fn empty() -> Self;
+    fn is_empty(&self) -> bool;
+    /// Adds a DER certificate, failing if it cannot be parsed.
+    fn add_der(&mut self, certificate: Vec<u8>) -> Result<(), String>;
+    /// Adds the DER certificates which can be parsed, skipping the others.
+    fn add_parsable(&mut self, certificates: &[Vec<u8>]);
+    fn add_webpki_roots(&mut self);
+}
+
+impl RootStore for RootCertStore {
+    fn empty() -> Self {
+        RootCertStore::empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        RootCertStore::is_empty(self)
+    }
+
+    fn add_der(&mut self, certificate: Vec<u8>) -> Result<(), String> {
+        self.add(&Certificate(certificate))
+            .map_err(|err| err.to_string())
+    }
+
+    fn add_parsable(&mut self, certificates: &[Vec<u8>]) {
+        self.add_parsable_certificates(certificates);
+    }
+
+    fn add_webpki_roots(&mut self) {
+        self.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    }
+}
+
+impl RootStore for anchors::RootCertStore {
+    fn empty() -> Self {
+        anchors::RootCertStore::empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        anchors::RootCertStore::is_empty(self)
+    }
+
+    fn add_der(&mut self, certificate: Vec<u8>) -> Result<(), String> {
+        self.add(&key::Certificate(certificate))
+            .map_err(|err| err.to_string())
+    }
+
+    fn add_parsable(&mut self, certificates: &[Vec<u8>]) {
+        self.add_parsable_certificates(certificates);
+    }
+
+    fn add_webpki_roots(&mut self) {
+        self.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+            anchors::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+    }
+}
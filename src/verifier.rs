@@ -0,0 +1,220 @@
+use crate::{range::RangeSet, TrustStore, TrustStoreError, TrustedNotaries};
+use chrono::{DateTime, Utc};
+use elliptic_curve::pkcs8::{EncodePublicKey, LineEnding};
+use serde::{Serialize, Serializer};
+use tls_core::verify::{ServerCertVerifier, WebPkiVerifier};
+use tlsn_core::{
+    proof::{SessionProof, SessionProofError, SubstringsProofError, TlsProof},
+    transcript::RedactedTranscript,
+    ServerName,
+};
+
+/// Errors raised while verifying a [`TlsProof`].
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    #[error("failed to load the server trust store: {0}")]
+    TrustStore(#[from] TrustStoreError),
+    #[error("no notary key is trusted")]
+    NoTrustedNotary,
+    #[error("session proof does not verify against any trusted notary key: {0}")]
+    Session(#[from] SessionProofError),
+    #[error("substrings proof does not verify against the session header: {0}")]
+    Substrings(#[from] SubstringsProofError),
+    #[error("invalid session time {0}")]
+    InvalidTime(u64),
+}
+
+/// A transcript as disclosed by the prover.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifiedTranscript {
+    /// The transcript bytes. Redacted bytes are set to zero.
+    pub data: Vec<u8>,
+    /// The ranges the prover chose not to disclose.
+    pub redacted: RangeSet,
+}
+
+impl VerifiedTranscript {
+    fn new(transcript: RedactedTranscript) -> Self {
+        Self {
+            redacted: transcript.redacted().iter_ranges().collect(),
+            data: transcript.data().to_vec(),
+        }
+    }
+
+    /// Returns the transcript as text, with redacted bytes replaced by `placeholder`.
+    pub fn to_string_lossy(&self, placeholder: u8) -> String {
+        let mut data = self.data.clone();
+        for range in &self.redacted {
+            data[range.clone()].fill(placeholder);
+        }
+        String::from_utf8_lossy(&data).into_owned()
+    }
+}
+
+/// The verified contents of a [`TlsProof`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifiedPresentation {
+    /// Name of the server the session was held with, as checked against its certificate chain.
+    pub server_name: String,
+    /// Time at which the session was notarized.
+    pub time: DateTime<Utc>,
+    /// Data sent by the prover to the server.
+    pub sent: VerifiedTranscript,
+    /// Data received by the prover from the server.
+    pub recv: VerifiedTranscript,
+    /// Key of the notary which signed the session.
+    #[serde(serialize_with = "serialize_public_key")]
+    pub notary_key: p256::PublicKey,
+}
+
+/// Verifies `proof` against the notary keys in `trust`.
+///
+/// The identity of the server is checked with a certificate verifier which trusts the root
+/// certificates from the `webpki-roots` crate.
+pub fn verify(
+    proof: TlsProof,
+    trust: &TrustedNotaries,
+) -> Result<VerifiedPresentation, VerifyError> {
+    verify_with_trust_store(proof, trust, &TrustStore::WebPki)
+}
+
+/// Like [`verify`], but checks the identity of the server against the root certificates of
+/// `server_roots`, e.g. the CA of a staging or internal server.
+pub fn verify_with_trust_store(
+    proof: TlsProof,
+    trust: &TrustedNotaries,
+    server_roots: &TrustStore,
+) -> Result<VerifiedPresentation, VerifyError> {
+    let cert_verifier = WebPkiVerifier::new(server_roots.server_root_cert_store()?, None);
+    verify_with_cert_verifier(proof, trust, &cert_verifier)
+}
+
+/// Like [`verify`], but checks the certificate chain of the server with `cert_verifier`.
+pub fn verify_with_cert_verifier(
+    proof: TlsProof,
+    trust: &TrustedNotaries,
+    cert_verifier: &impl ServerCertVerifier,
+) -> Result<VerifiedPresentation, VerifyError> {
+    let TlsProof {
+        // The session proof establishes the identity of the server and the commitments
+        // to the TLS transcript.
+        session,
+        // The substrings proof proves select portions of the transcript, while redacting
+        // anything the Prover chose not to disclose.
+        substrings,
+    } = proof;
+
+    let notary_key = signing_key(&session, trust, cert_verifier)?;
+
+    let SessionProof {
+        // The session header that was signed by the Notary is a succinct commitment to the TLS transcript.
+        header,
+        // This is the session_info, which contains the server_name, that is checked against the
+        // certificate chain shared in the TLS handshake.
+        session_info,
+        ..
+    } = session;
+
+    let time = session_time(header.time())?;
+
+    // Verify the substrings proof against the session header.
+    //
+    // This returns the redacted transcripts
+    let (sent, recv) = substrings.verify(&header)?;
+
+    let server_name = match session_info.server_name {
+        ServerName::Dns(server_name) => server_name,
+    };
+
+    Ok(VerifiedPresentation {
+        server_name,
+        time,
+        sent: VerifiedTranscript::new(sent),
+        recv: VerifiedTranscript::new(recv),
+        notary_key,
+    })
+}
+
+/// Returns the key of `trust` which signed `session`, checking the server identity with
+/// `cert_verifier`.
+pub(crate) fn signing_key(
+    session: &SessionProof,
+    trust: &TrustedNotaries,
+    cert_verifier: &impl ServerCertVerifier,
+) -> Result<p256::PublicKey, VerifyError> {
+    // Verify the session proof against the first trusted Notary key it was signed with
+    let mut result = Err(VerifyError::NoTrustedNotary);
+    for key in trust.keys() {
+        match session.verify(*key, cert_verifier) {
+            Ok(()) => {
+                result = Ok(*key);
+                break;
+            }
+            Err(err) => result = Err(err.into()),
+        }
+    }
+    result
+}
+
+/// The contents of a [`TlsProof`], as claimed by the proof itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProofSummary {
+    /// Name of the server the session claims to have been held with.
+    pub server_name: String,
+    /// Time at which the session claims to have been notarized.
+    pub time: DateTime<Utc>,
+    /// Data sent by the prover to the server.
+    pub sent: VerifiedTranscript,
+    /// Data received by the prover from the server.
+    pub recv: VerifiedTranscript,
+    /// Whether the session header carries a notary signature.
+    pub signed: bool,
+}
+
+/// Decodes the revealed transcripts of `proof` without checking who signed it.
+///
+/// The revealed data is only checked against the session header. Neither the notary signature
+/// nor the identity of the server is verified, so the result must not be trusted; use
+/// [`verify`] for that.
+pub fn inspect(proof: TlsProof) -> Result<ProofSummary, VerifyError> {
+    let TlsProof {
+        session,
+        substrings,
+    } = proof;
+    let SessionProof {
+        header,
+        session_info,
+        signature,
+    } = session;
+
+    let time = session_time(header.time())?;
+    let (sent, recv) = substrings.verify(&header)?;
+    let server_name = match session_info.server_name {
+        ServerName::Dns(server_name) => server_name,
+    };
+
+    Ok(ProofSummary {
+        server_name,
+        time,
+        sent: VerifiedTranscript::new(sent),
+        recv: VerifiedTranscript::new(recv),
+        signed: signature.is_some(),
+    })
+}
+
+fn session_time(secs: u64) -> Result<DateTime<Utc>, VerifyError> {
+    i64::try_from(secs)
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .ok_or(VerifyError::InvalidTime(secs))
+}
+
+fn serialize_public_key<S: Serializer>(
+    key: &p256::PublicKey,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let pem = key
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(serde::ser::Error::custom)?;
+    serializer.serialize_str(&pem)
+}
@@ -0,0 +1,179 @@
+//! Offline stand-ins for a notary and an HTTPS server, so that a whole notarization can run in
+//! tests without network access.
+
+// Each test crate uses its own part of the harness
+#![allow(dead_code)]
+
+use elliptic_curve::pkcs8::{EncodePrivateKey, LineEnding};
+use opacity::{
+    notary::{NotaryHandle, NotaryServerConfig, NotaryTls},
+    DataLimits, NotaryConfig, TrustStore,
+};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType, IsCa};
+use std::{path::Path, sync::Arc, time::Duration};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::TlsAcceptor;
+
+/// Host name of both stand-ins, as found in their certificate.
+pub const HOST: &str = "localhost";
+/// Body of every response of the HTTPS server.
+pub const RESPONSE_BODY: &str = r#"{"account":{"id":"acct_42","balance":1337},"status":"ok"}"#;
+
+/// A notary and an HTTPS server running in the background of the current runtime.
+pub struct Harness {
+    /// Stops the notary when dropped.
+    notary_handle: Option<NotaryHandle>,
+    /// Holds the keys and certificates read by the notary.
+    _dir: TempDir,
+    /// Config reaching the notary, with its signing key pinned.
+    pub notary: NotaryConfig,
+    pub notary_key: p256::PublicKey,
+    /// Port of the HTTPS server.
+    pub server_port: u16,
+    /// Trust store authenticating both the notary and the HTTPS server.
+    pub trust_store: TrustStore,
+}
+
+impl Harness {
+    pub async fn start() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let pki = TestPki::generate();
+        pki.write(dir.path());
+
+        let mut config = NotaryServerConfig::new(dir.path().join("notary.key"));
+        config.host = "127.0.0.1".to_string();
+        config.port = 0;
+        config.tls = Some(NotaryTls {
+            certificate_path: dir.path().join("tls.crt"),
+            private_key_path: dir.path().join("tls.key"),
+        });
+        config.limits = DataLimits {
+            max_sent_data: 1 << 14,
+            max_recv_data: 1 << 14,
+        };
+        let handle = opacity::notary::spawn(config).await.unwrap();
+
+        let server_port = spawn_https_server(&pki).await;
+
+        let trust_store = TrustStore::Pem(pki.ca_pem.clone().into_bytes());
+        let mut notary = handle.client_config(HOST);
+        notary.trust_store = trust_store.clone();
+
+        Self {
+            _dir: dir,
+            notary_key: *handle.public_key(),
+            notary_handle: Some(handle),
+            notary,
+            server_port,
+            trust_store,
+        }
+    }
+
+    /// Stops the notary and waits until it refuses connections.
+    pub async fn stop_notary(&mut self) {
+        self.notary_handle = None;
+        while TcpStream::connect(("127.0.0.1", self.notary.port))
+            .await
+            .is_ok()
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+/// A CA, a certificate it issued for [`HOST`] and a notary signing key.
+struct TestPki {
+    ca_pem: String,
+    cert_pem: String,
+    cert_der: Vec<u8>,
+    key_pem: String,
+    key_der: Vec<u8>,
+    notary_private_key_pem: String,
+}
+
+impl TestPki {
+    fn generate() -> Self {
+        let mut ca_params = CertificateParams::new(Vec::new());
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params.distinguished_name = DistinguishedName::new();
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, "opacity test CA");
+        let ca = Certificate::from_params(ca_params).unwrap();
+
+        let cert =
+            Certificate::from_params(CertificateParams::new(vec![HOST.to_string()])).unwrap();
+
+        let notary_secret = p256::SecretKey::random(&mut rand::rngs::OsRng);
+
+        Self {
+            ca_pem: ca.serialize_pem().unwrap(),
+            cert_pem: cert.serialize_pem_with_signer(&ca).unwrap(),
+            cert_der: cert.serialize_der_with_signer(&ca).unwrap(),
+            key_pem: cert.serialize_private_key_pem(),
+            key_der: cert.serialize_private_key_der(),
+            notary_private_key_pem: notary_secret
+                .to_pkcs8_pem(LineEnding::LF)
+                .unwrap()
+                .to_string(),
+        }
+    }
+
+    fn write(&self, dir: &Path) {
+        std::fs::write(dir.join("tls.crt"), &self.cert_pem).unwrap();
+        std::fs::write(dir.join("tls.key"), &self.key_pem).unwrap();
+        std::fs::write(dir.join("notary.key"), &self.notary_private_key_pem).unwrap();
+    }
+}
+
+/// Starts an HTTPS server answering every request with [`RESPONSE_BODY`], and returns its port.
+async fn spawn_https_server(pki: &TestPki) -> u16 {
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![rustls::Certificate(pki.cert_der.clone())],
+            rustls::PrivateKey(pki.key_der.clone()),
+        )
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(mut stream) = acceptor.accept(socket).await else {
+                    return;
+                };
+
+                // Requests of the tests carry no body, so the head is the whole request
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    RESPONSE_BODY.len(),
+                    RESPONSE_BODY
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            });
+        }
+    });
+
+    port
+}
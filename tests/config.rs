@@ -0,0 +1,307 @@
+use opacity::{ConfigError, ConfigSource, NotaryConfig, NotaryEndpoint, RetryClass, CONFIG_KEYS};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+use tempfile::TempDir;
+
+/// Serializes the tests reading or writing the process environment.
+static ENV: Mutex<()> = Mutex::new(());
+
+/// Exclusive access to the `NOTARY_*` variables of the process environment, cleared on creation
+/// and on drop.
+struct EnvGuard {
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl EnvGuard {
+    fn lock() -> Self {
+        let guard = EnvGuard {
+            _lock: ENV.lock().unwrap_or_else(|err| err.into_inner()),
+        };
+        clear_env();
+        guard
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        env::set_var(format!("NOTARY_{}", key.to_uppercase()), value);
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        clear_env();
+    }
+}
+
+fn clear_env() {
+    for key in CONFIG_KEYS {
+        env::remove_var(format!("NOTARY_{}", key.to_uppercase()));
+    }
+}
+
+fn write(dir: &TempDir, name: &str, contents: &str) -> PathBuf {
+    let path = dir.path().join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn load_file(path: &Path) -> Result<NotaryConfig, ConfigError> {
+    let _env = EnvGuard::lock();
+    NotaryConfig::loader().dotenv(false).file(path).load()
+}
+
+#[test]
+fn each_layer_overrides_the_previous_ones() {
+    let dir = TempDir::new().unwrap();
+    let dotenv = write(
+        &dir,
+        ".env",
+        "NOTARY_HOST=dotenv\nNOTARY_PORT=1\nNOTARY_MAX_SENT_DATA=1\nNOTARY_MAX_RECV_DATA=1\n\
+         NOTARY_PREFLIGHT=true\n",
+    );
+    let file = write(&dir, "notary.toml", "max_recv_data = 3\nport = 3\n");
+
+    let env = EnvGuard::lock();
+    env.set("port", "2");
+    env.set("max_sent_data", "2");
+    env.set("max_recv_data", "2");
+
+    let config = NotaryConfig::loader()
+        .dotenv_file(&dotenv)
+        .file(&file)
+        .set("port", "4")
+        .load()
+        .unwrap();
+
+    // Only set in `.env`
+    assert_eq!(config.host, "dotenv");
+    assert!(config.preflight);
+    // The environment wins over `.env`
+    assert_eq!(config.max_sent_data, 2);
+    // The file wins over the environment
+    assert_eq!(config.max_recv_data, 3);
+    // Overrides win over everything
+    assert_eq!(config.port, 4);
+}
+
+#[test]
+fn environment_is_read_without_dotenv() {
+    let env = EnvGuard::lock();
+    env.set("host", "notary.example.com");
+    env.set("port", "7047");
+    env.set("tls", "off");
+
+    let config = NotaryConfig::loader().dotenv(false).load().unwrap();
+    assert_eq!(config.host, "notary.example.com");
+    assert_eq!(config.port, 7047);
+    assert!(!config.tls);
+}
+
+#[test]
+fn dotenv_ignores_unrelated_and_unknown_variables() {
+    let dir = TempDir::new().unwrap();
+    let dotenv = write(
+        &dir,
+        ".env",
+        "RUST_LOG=debug\nNOTARY_UNKNOWN=1\nNOTARY_HOST=localhost\nNOTARY_PORT=7047\n",
+    );
+
+    let _env = EnvGuard::lock();
+    let config = NotaryConfig::loader().dotenv_file(dotenv).load().unwrap();
+    assert_eq!(config.host, "localhost");
+}
+
+#[test]
+fn explicit_dotenv_file_must_exist() {
+    let dir = TempDir::new().unwrap();
+
+    let _env = EnvGuard::lock();
+    let err = NotaryConfig::loader()
+        .dotenv_file(dir.path().join(".env"))
+        .load()
+        .unwrap_err();
+    assert!(matches!(err, ConfigError::DotEnv(_)), "{err}");
+}
+
+#[test]
+fn missing_required_key_is_named() {
+    let _env = EnvGuard::lock();
+    let err = NotaryConfig::loader()
+        .dotenv(false)
+        .set("port", "7047")
+        .load()
+        .unwrap_err();
+    assert!(matches!(err, ConfigError::Missing { key: "host" }), "{err}");
+    assert!(err.to_string().contains("$NOTARY_HOST"), "{err}");
+}
+
+#[test]
+fn invalid_value_reports_key_and_origin() {
+    let dir = TempDir::new().unwrap();
+    let file = write(
+        &dir,
+        "notary.toml",
+        "host = \"localhost\"\nport = 7047\nmax_sent_data = -1\n",
+    );
+
+    match load_file(&file).unwrap_err() {
+        ConfigError::Invalid {
+            key, value, origin, ..
+        } => {
+            assert_eq!(key, "max_sent_data");
+            assert_eq!(value, "-1");
+            assert_eq!(origin, ConfigSource::File(file));
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    let env = EnvGuard::lock();
+    env.set("host", "localhost");
+    env.set("port", "notaport");
+    match NotaryConfig::loader().dotenv(false).load().unwrap_err() {
+        ConfigError::Invalid { key, origin, .. } => {
+            assert_eq!(key, "port");
+            assert_eq!(origin, ConfigSource::Env);
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn unknown_keys_are_refused() {
+    let dir = TempDir::new().unwrap();
+    let file = write(&dir, "notary.toml", "hots = \"localhost\"\n");
+    match load_file(&file).unwrap_err() {
+        ConfigError::UnknownKey { key, origin } => {
+            assert_eq!(key, "hots");
+            assert_eq!(origin, ConfigSource::File(file));
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+
+    let _env = EnvGuard::lock();
+    match NotaryConfig::loader()
+        .dotenv(false)
+        .set("prot", "7047")
+        .load()
+        .unwrap_err()
+    {
+        ConfigError::UnknownKey { key, origin } => {
+            assert_eq!(key, "prot");
+            assert_eq!(origin, ConfigSource::Override);
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn overrides_are_parsed_as_key_value() {
+    let _env = EnvGuard::lock();
+    let config = NotaryConfig::loader()
+        .dotenv(false)
+        .set_str(" host = localhost ")
+        .unwrap()
+        .set_str("port=7047")
+        .unwrap()
+        .set_str("public_key_path=keys/a=b.pem")
+        .unwrap()
+        .load()
+        .unwrap();
+    assert_eq!(config.host, "localhost");
+    assert_eq!(config.port, 7047);
+    assert_eq!(config.public_key_path, Some(PathBuf::from("keys/a=b.pem")));
+
+    let err = NotaryConfig::loader().set_str("port").unwrap_err();
+    assert!(
+        matches!(&err, ConfigError::MalformedOverride(value) if value == "port"),
+        "{err}"
+    );
+}
+
+#[test]
+fn later_override_of_a_key_wins() {
+    let _env = EnvGuard::lock();
+    let config = NotaryConfig::loader()
+        .dotenv(false)
+        .set("host", "localhost")
+        .set("port", "1")
+        .set("port", "2")
+        .load()
+        .unwrap();
+    assert_eq!(config.port, 2);
+}
+
+#[test]
+fn toml_lists_are_read_as_comma_separated_values() {
+    let dir = TempDir::new().unwrap();
+    let file = write(
+        &dir,
+        "notary.toml",
+        r#"
+host = "notary-a"
+port = 7047
+tls = false
+notaries = ["notary-b:7047", "notary-c:7048@2"]
+retry_on = ["notary", "server_status"]
+unhealthy_cooldown_secs = 5
+"#,
+    );
+
+    let config = load_file(&file).unwrap();
+    assert!(!config.tls);
+    assert_eq!(config.unhealthy_cooldown, Duration::from_secs(5));
+    assert_eq!(
+        config.notaries,
+        [
+            NotaryEndpoint {
+                host: "notary-b".to_string(),
+                port: 7047,
+                priority: 1,
+            },
+            NotaryEndpoint {
+                host: "notary-c".to_string(),
+                port: 7048,
+                priority: 2,
+            },
+        ]
+    );
+    assert_eq!(
+        config.retry.retry_on,
+        [RetryClass::Notary, RetryClass::ServerStatus]
+    );
+}
+
+#[test]
+fn empty_toml_list_retries_nothing() {
+    let dir = TempDir::new().unwrap();
+    let file = write(
+        &dir,
+        "notary.toml",
+        "host = \"localhost\"\nport = 7047\nretry_on = []\n",
+    );
+    assert!(load_file(&file).unwrap().retry.retry_on.is_empty());
+}
+
+#[test]
+fn toml_list_of_non_strings_is_refused() {
+    let dir = TempDir::new().unwrap();
+    let file = write(
+        &dir,
+        "notary.toml",
+        "host = \"localhost\"\nport = 7047\nnotaries = [7048]\n",
+    );
+    let err = load_file(&file).unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ConfigError::Invalid {
+                key: "notaries",
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
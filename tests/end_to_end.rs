@@ -0,0 +1,167 @@
+//! Notarizes a request to a local HTTPS server through a local notary, then verifies the proof,
+//! without network access.
+//!
+//! The MPC-TLS session is slow without optimizations, run with `cargo test --release`.
+
+mod common;
+
+use common::{Harness, HOST, RESPONSE_BODY};
+use opacity::{
+    key_fingerprint, notarize, notarize_envelope, verify_with_trust_store, Direction, JsonSelector,
+    NotarizationRequest, NotarizeOptions, OpacityError, PolicyRule, ProofEnvelope, ProofFormat,
+    Redaction, RedactionKind, RedactionMatch, TrustedNotaries, VerificationPolicy,
+};
+
+fn request(harness: &Harness) -> NotarizationRequest {
+    serde_json::from_value(serde_json::json!({
+        "host": HOST,
+        "port": harness.server_port,
+        "path": "/account",
+        "headers": [
+            ["Host", HOST],
+            ["Accept", "application/json"],
+            ["Authorization", "Bearer test-token"],
+            ["Connection", "close"],
+        ],
+        "redactions": [{ "direction": "sent", "header": "Authorization" }],
+    }))
+    .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn prove_then_verify() {
+    let harness = Harness::start().await;
+    let options = NotarizeOptions::new(harness.notary.clone())
+        .server_trust_store(harness.trust_store.clone());
+
+    let envelope = notarize_envelope(&request(&harness), &options)
+        .await
+        .unwrap();
+    assert_eq!(
+        envelope.metadata.notary_key_fingerprint,
+        Some(key_fingerprint(&harness.notary_key))
+    );
+
+    // The proof must survive its encoding
+    let encoded = ProofFormat::Cbor.encode(&envelope).unwrap();
+    let envelope = ProofEnvelope::decode(&encoded).unwrap();
+
+    let presentation = verify_with_trust_store(
+        envelope.proof,
+        &TrustedNotaries::from(harness.notary_key),
+        &harness.trust_store,
+    )
+    .unwrap();
+
+    assert_eq!(presentation.server_name, HOST);
+    assert_eq!(presentation.notary_key, harness.notary_key);
+    let sent = presentation.sent.to_string_lossy(b'X');
+    assert!(sent.starts_with("GET /account HTTP/1.1\r\n"));
+    assert!(!sent.contains("test-token"));
+    assert!(presentation
+        .recv
+        .to_string_lossy(b'X')
+        .ends_with(RESPONSE_BODY));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn disclosed_fields_meet_the_policy() {
+    let harness = Harness::start().await;
+    let options = NotarizeOptions::new(harness.notary.clone())
+        .server_trust_store(harness.trust_store.clone());
+    let mut request = request(&harness);
+    request
+        .disclose
+        .push(JsonSelector::Pointer("/account/balance".to_string()));
+
+    let proof = notarize(&request, &options).await.unwrap();
+    let presentation = verify_with_trust_store(
+        proof,
+        &TrustedNotaries::from(harness.notary_key),
+        &harness.trust_store,
+    )
+    .unwrap();
+    assert!(!presentation.recv.to_string_lossy(b'X').contains("acct_42"));
+
+    let policy = VerificationPolicy {
+        required_fields: vec!["/account/balance".to_string(), "/account/id".to_string()],
+        ..Default::default()
+    };
+    let report = policy.evaluate(&presentation);
+    let failures: Vec<_> = report.failures().map(|result| &result.rule).collect();
+    assert_eq!(
+        failures,
+        [&PolicyRule::RequiredField("/account/id".to_string())]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn untrusted_notary_key_is_refused() {
+    let harness = Harness::start().await;
+    let options = NotarizeOptions::new(harness.notary.clone())
+        .server_trust_store(harness.trust_store.clone());
+    let envelope = notarize_envelope(&request(&harness), &options)
+        .await
+        .unwrap();
+
+    let other_key = p256::SecretKey::random(&mut rand::rngs::OsRng).public_key();
+    assert!(verify_with_trust_store(
+        envelope.proof,
+        &TrustedNotaries::from(other_key),
+        &harness.trust_store,
+    )
+    .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn envelope_does_not_record_redacted_literals() {
+    const SECRET: &str = "s3cret-query";
+
+    let harness = Harness::start().await;
+    let options = NotarizeOptions::new(harness.notary.clone())
+        .server_trust_store(harness.trust_store.clone());
+    let mut request = request(&harness);
+    request.path = format!("/account?key={SECRET}");
+    request.redactions.push(Redaction::new(
+        Direction::Sent,
+        RedactionMatch::Literal(SECRET.to_string()),
+    ));
+
+    let envelope = notarize_envelope(&request, &options).await.unwrap();
+    let literal = &envelope.metadata.redactions[1];
+    assert_eq!(literal.kind, RedactionKind::Literal);
+    assert_eq!(literal.ranges.len(), 1);
+    assert_eq!(literal.ranges[0].len(), SECRET.len());
+
+    for format in [ProofFormat::Json, ProofFormat::Cbor] {
+        let encoded = format.encode(&envelope).unwrap();
+        assert!(
+            !encoded
+                .windows(SECRET.len())
+                .any(|window| window == SECRET.as_bytes()),
+            "the {format} envelope holds the literal"
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn response_beyond_the_preflight_ceiling_is_refused_up_front() {
+    let harness = Harness::start().await;
+    let mut notary = harness.notary.clone();
+    notary.preflight = true;
+    notary.max_recv_data_ceiling = 64;
+    let options = NotarizeOptions::new(notary).server_trust_store(harness.trust_store.clone());
+
+    let err = notarize(&request(&harness), &options).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            OpacityError::DataLimitExceeded {
+                direction: Direction::Recv,
+                needed,
+                limit: 64,
+            } if needed > RESPONSE_BODY.len()
+        ),
+        "{err}"
+    );
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{Harness, HOST};
+use opacity::{tls_prover, DataLimits, NotaryConfig, NotaryEndpoint, OpacityError};
+
+/// Returns a port nothing listens on.
+fn closed_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn fails_over_to_the_next_notary() {
+    let harness = Harness::start().await;
+    let live = NotaryEndpoint {
+        host: HOST.to_string(),
+        port: harness.notary.port,
+        priority: 1,
+    };
+    let mut config = harness.notary.clone();
+    config.port = closed_port();
+    config.notaries = vec![live.clone()];
+
+    let session = tls_prover(&config, DataLimits::default()).await.unwrap();
+    assert_eq!(session.notary, live);
+    assert!(!session.session_id.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_every_failure() {
+    let mut config = NotaryConfig::new(HOST, closed_port());
+    config.notaries = vec![format!("{HOST}:{}", closed_port()).parse().unwrap()];
+
+    match tls_prover(&config, DataLimits::default()).await {
+        Err(OpacityError::AllNotariesFailed(errors)) => assert_eq!(errors.len(), 2),
+        other => panic!("expected every notary to fail, got {:?}", other.err()),
+    }
+}
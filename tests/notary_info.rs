@@ -0,0 +1,51 @@
+mod common;
+
+use common::Harness;
+use opacity::{fetch_info, InfoClient, NotaryConfig, NotaryKeyError, TrustedNotaries};
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn info_reports_the_signing_key() {
+    let harness = Harness::start().await;
+
+    let info = fetch_info(&harness.notary).await.unwrap();
+    assert_eq!(info.signing_key().unwrap(), harness.notary_key);
+    assert!(!info.version.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn info_is_served_from_the_cache_until_it_expires() {
+    let mut harness = Harness::start().await;
+    let cached = InfoClient::new(Duration::from_secs(60));
+    let expired = InfoClient::new(Duration::ZERO);
+    let info = cached.info(&harness.notary).await.unwrap();
+    expired.info(&harness.notary).await.unwrap();
+
+    harness.stop_notary().await;
+    assert!(fetch_info(&harness.notary).await.is_err());
+    assert_eq!(cached.info(&harness.notary).await.unwrap(), info);
+    assert!(expired.info(&harness.notary).await.is_err());
+
+    cached.invalidate(&harness.notary);
+    assert!(cached.info(&harness.notary).await.is_err());
+}
+
+#[tokio::test]
+async fn keys_are_not_fetched_without_tls() {
+    // Nothing listens there: the refusal must come before any connection
+    let mut config = NotaryConfig::new("127.0.0.1", 1);
+    config.tls = false;
+    config.fetch_public_key = true;
+
+    let insecure = |err: NotaryKeyError| matches!(err, NotaryKeyError::InsecureFetch { .. });
+    assert!(insecure(fetch_info(&config).await.unwrap_err()));
+    assert!(insecure(
+        InfoClient::new(Duration::from_secs(60))
+            .info(&config)
+            .await
+            .unwrap_err()
+    ));
+    assert!(insecure(
+        TrustedNotaries::from_config(&config).await.unwrap_err()
+    ));
+}
@@ -0,0 +1,74 @@
+use opacity::RangeSet;
+use proptest::prelude::*;
+use std::{collections::BTreeSet, ops::Range};
+
+const UNIVERSE: usize = 64;
+
+fn ranges() -> impl Strategy<Value = Vec<Range<usize>>> {
+    prop::collection::vec((0..UNIVERSE, 0..UNIVERSE).prop_map(|(a, b)| a..b), 0..8)
+}
+
+fn model(ranges: &[Range<usize>]) -> BTreeSet<usize> {
+    ranges.iter().flat_map(|range| range.clone()).collect()
+}
+
+fn offsets(set: &RangeSet) -> BTreeSet<usize> {
+    set.iter().flat_map(|range| range.clone()).collect()
+}
+
+fn assert_normalized(set: &RangeSet) {
+    for range in set {
+        assert!(!range.is_empty(), "empty range in {set:?}");
+    }
+    for pair in set.iter().collect::<Vec<_>>().windows(2) {
+        assert!(pair[0].end < pair[1].start, "unmerged ranges in {set:?}");
+    }
+}
+
+proptest! {
+    #[test]
+    fn insert_matches_model(a in ranges()) {
+        let set: RangeSet = a.iter().cloned().collect();
+        assert_normalized(&set);
+        prop_assert_eq!(offsets(&set), model(&a));
+        prop_assert_eq!(set.len(), model(&a).len());
+    }
+
+    #[test]
+    fn union_matches_model(a in ranges(), b in ranges()) {
+        let set = RangeSet::from_iter(a.clone()).union(&RangeSet::from_iter(b.clone()));
+        assert_normalized(&set);
+        prop_assert_eq!(offsets(&set), &model(&a) | &model(&b));
+    }
+
+    #[test]
+    fn intersection_matches_model(a in ranges(), b in ranges()) {
+        let set = RangeSet::from_iter(a.clone()).intersection(&RangeSet::from_iter(b.clone()));
+        assert_normalized(&set);
+        prop_assert_eq!(offsets(&set), &model(&a) & &model(&b));
+    }
+
+    #[test]
+    fn difference_matches_model(a in ranges(), b in ranges()) {
+        let set = RangeSet::from_iter(a.clone()).difference(&RangeSet::from_iter(b.clone()));
+        assert_normalized(&set);
+        prop_assert_eq!(offsets(&set), &model(&a) - &model(&b));
+    }
+
+    #[test]
+    fn complement_never_leaks_private_bytes(private in ranges(), start in 0..UNIVERSE, end in 0..UNIVERSE) {
+        let private_set = RangeSet::from_iter(private.clone());
+        let public = private_set.complement(start..end);
+        assert_normalized(&public);
+
+        let expected: BTreeSet<usize> = (start..end).filter(|o| !model(&private).contains(o)).collect();
+        prop_assert_eq!(offsets(&public), expected);
+        prop_assert!(public.intersection(&private_set).is_empty());
+    }
+
+    #[test]
+    fn contains_matches_model(a in ranges(), offset in 0..UNIVERSE) {
+        let set = RangeSet::from_iter(a.clone());
+        prop_assert_eq!(set.contains(offset), model(&a).contains(&offset));
+    }
+}
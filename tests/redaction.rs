@@ -0,0 +1,220 @@
+use opacity::{Direction, HttpPart, Redaction, RedactionError, RedactionMatch};
+
+const SENT: &str = "POST /login?user=alice HTTP/1.1\r\n\
+                    Host: api.example.com\r\n\
+                    Authorization: Bearer s3cret-token\r\n\
+                    X-Api-Key:  k-123 \r\n\
+                    Content-Type: application/json\r\n\
+                    Content-Length: 37\r\n\
+                    \r\n\
+                    {\"user\":\"alice\",\"password\":\"hunter2\"}";
+
+const RECV: &str = "HTTP/1.1 200 OK\r\n\
+                    Content-Type: application/json\r\n\
+                    Set-Cookie: session=s3cret\r\n\
+                    set-cookie: theme=dark\r\n\
+                    Content-Length: 71\r\n\
+                    \r\n\
+                    {\"account\":{\"id\":7,\"balance\":337},\"tokens\":[\"t1\",\"t2\"],\"owner\":\"alice\"}";
+
+/// Returns the parts of the transcript of `direction` hidden by the rule.
+fn hidden(direction: Direction, target: RedactionMatch) -> Vec<&'static str> {
+    let transcript = match direction {
+        Direction::Sent => SENT,
+        Direction::Recv => RECV,
+    };
+    Redaction::new(direction, target)
+        .resolve(transcript.as_bytes())
+        .unwrap()
+        .into_iter()
+        .map(|range| &transcript[range])
+        .collect()
+}
+
+fn literal(value: &str) -> RedactionMatch {
+    RedactionMatch::Literal(value.to_string())
+}
+
+fn regex(pattern: &str) -> RedactionMatch {
+    RedactionMatch::Regex(pattern.to_string())
+}
+
+fn header(name: &str) -> RedactionMatch {
+    RedactionMatch::Header(name.to_string())
+}
+
+fn pointer(pointer: &str) -> RedactionMatch {
+    RedactionMatch::JsonPointer(pointer.to_string())
+}
+
+#[test]
+fn transcripts_are_consistent() {
+    for transcript in [SENT, RECV] {
+        let (head, body) = transcript.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+    }
+}
+
+#[test]
+fn literal_hides_every_occurrence() {
+    assert_eq!(
+        hidden(Direction::Sent, literal("alice")),
+        ["alice", "alice"]
+    );
+    assert_eq!(hidden(Direction::Recv, literal("alice")), ["alice"]);
+    assert_eq!(hidden(Direction::Recv, literal("s3cret")), ["s3cret"]);
+    assert!(hidden(Direction::Recv, literal("hunter2")).is_empty());
+}
+
+#[test]
+fn empty_literal_is_refused() {
+    let err = Redaction::new(Direction::Sent, literal(""))
+        .resolve(SENT.as_bytes())
+        .unwrap_err();
+    assert!(matches!(err, RedactionError::EmptyLiteral), "{err}");
+}
+
+#[test]
+fn regex_hides_every_match() {
+    assert_eq!(
+        hidden(Direction::Sent, regex(r"Bearer \S+")),
+        ["Bearer s3cret-token"]
+    );
+    assert_eq!(
+        hidden(Direction::Recv, regex(r"(session|theme)=\w+")),
+        ["session=s3cret", "theme=dark"]
+    );
+}
+
+#[test]
+fn invalid_regex_is_refused() {
+    let err = Redaction::new(Direction::Sent, regex("("))
+        .resolve(SENT.as_bytes())
+        .unwrap_err();
+    assert!(matches!(err, RedactionError::InvalidRegex(_)), "{err}");
+}
+
+#[test]
+fn header_hides_trimmed_values_of_every_header_with_the_name() {
+    assert_eq!(
+        hidden(Direction::Sent, header("authorization")),
+        ["Bearer s3cret-token"]
+    );
+    assert_eq!(hidden(Direction::Sent, header("X-API-KEY")), ["k-123"]);
+    assert_eq!(
+        hidden(Direction::Recv, header("Set-Cookie")),
+        ["session=s3cret", "theme=dark"]
+    );
+    assert!(hidden(Direction::Recv, header("Authorization")).is_empty());
+}
+
+#[test]
+fn header_of_a_transcript_which_is_not_http_is_refused() {
+    let err = Redaction::new(Direction::Recv, header("Set-Cookie"))
+        .resolve(b"not http")
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            RedactionError::Http {
+                direction: Direction::Recv,
+                ..
+            }
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn json_pointer_hides_the_value_only() {
+    assert_eq!(
+        hidden(Direction::Sent, pointer("/password")),
+        ["\"hunter2\""]
+    );
+    assert_eq!(
+        hidden(Direction::Recv, pointer("/account/balance")),
+        ["337"]
+    );
+    assert_eq!(hidden(Direction::Recv, pointer("/tokens/1")), ["\"t2\""]);
+    assert_eq!(
+        hidden(Direction::Recv, pointer("/account")),
+        ["{\"id\":7,\"balance\":337}"]
+    );
+    assert!(hidden(Direction::Recv, pointer("/account/missing")).is_empty());
+    assert!(hidden(Direction::Recv, pointer("/tokens/2")).is_empty());
+}
+
+#[test]
+fn invalid_json_pointer_is_refused() {
+    let err = Redaction::new(Direction::Recv, pointer("account"))
+        .resolve(RECV.as_bytes())
+        .unwrap_err();
+    assert!(
+        matches!(&err, RedactionError::Json { pointer, .. } if pointer == "account"),
+        "{err}"
+    );
+}
+
+#[test]
+fn json_pointer_into_a_body_which_is_not_json_is_refused() {
+    let recv = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+    let err = Redaction::new(Direction::Recv, pointer("/a"))
+        .resolve(recv.as_bytes())
+        .unwrap_err();
+    assert!(matches!(err, RedactionError::Json { .. }), "{err}");
+}
+
+#[test]
+fn part_hides_a_whole_part_of_the_message() {
+    let part = RedactionMatch::Part;
+    assert_eq!(
+        hidden(Direction::Sent, part(HttpPart::StartLine)),
+        ["POST /login?user=alice HTTP/1.1"]
+    );
+    assert_eq!(
+        hidden(Direction::Recv, part(HttpPart::StartLine)),
+        ["HTTP/1.1 200 OK"]
+    );
+
+    let sent_headers = hidden(Direction::Sent, part(HttpPart::Headers));
+    assert_eq!(sent_headers.len(), 1);
+    assert!(sent_headers[0].starts_with("Host: api.example.com\r\n"));
+    assert!(sent_headers[0].ends_with("Content-Length: 37"));
+    let recv_headers = hidden(Direction::Recv, part(HttpPart::Headers));
+    assert_eq!(recv_headers.len(), 1);
+    assert!(recv_headers[0].starts_with("Content-Type: application/json\r\n"));
+    assert!(recv_headers[0].ends_with("Content-Length: 71"));
+
+    assert_eq!(
+        hidden(Direction::Sent, part(HttpPart::Body)),
+        [SENT.split_once("\r\n\r\n").unwrap().1]
+    );
+    assert_eq!(
+        hidden(Direction::Recv, part(HttpPart::Body)),
+        [RECV.split_once("\r\n\r\n").unwrap().1]
+    );
+}
+
+#[test]
+fn empty_parts_hide_nothing() {
+    let request = "GET / HTTP/1.1\r\n\r\n";
+    for part in [HttpPart::Headers, HttpPart::Body] {
+        let ranges = Redaction::new(Direction::Sent, RedactionMatch::Part(part))
+            .resolve(request.as_bytes())
+            .unwrap();
+        assert!(ranges.is_empty(), "{part:?}: {ranges:?}");
+    }
+}
+
+#[test]
+fn json_pointer_into_a_chunked_body_hides_the_value_in_every_chunk() {
+    let recv = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                d\r\n{\"balance\":33\r\n\
+                3\r\n7}\n\r\n\
+                0\r\n\r\n";
+    let ranges = Redaction::new(Direction::Recv, pointer("/balance"))
+        .resolve(recv.as_bytes())
+        .unwrap();
+    let hidden: Vec<_> = ranges.into_iter().map(|range| &recv[range]).collect();
+    assert_eq!(hidden, ["33", "7"]);
+}
@@ -0,0 +1,11 @@
+use opacity::NotarizationRequest;
+
+/// The published schema must be regenerated, e.g. with `opacity schema`, whenever the request
+/// format changes.
+#[test]
+fn published_schema_is_up_to_date() {
+    let published: serde_json::Value =
+        serde_json::from_str(include_str!("../schema/notarization-request.schema.json")).unwrap();
+    let generated = serde_json::to_value(NotarizationRequest::json_schema()).unwrap();
+    assert_eq!(published, generated);
+}
@@ -0,0 +1,94 @@
+use hyper::StatusCode;
+use opacity::{OpacityError, RetryClass, RetryPolicy};
+use std::{io, time::Duration};
+
+fn server_connect_error() -> OpacityError {
+    OpacityError::ServerConnect {
+        host: "localhost".to_string(),
+        port: 443,
+        source: io::ErrorKind::ConnectionRefused.into(),
+    }
+}
+
+fn without_jitter() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 5,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(300),
+        jitter_percent: 0,
+        ..RetryPolicy::default()
+    }
+}
+
+#[test]
+fn backoff_doubles_up_to_the_cap() {
+    let policy = without_jitter();
+    let err = server_connect_error();
+    let delays: Vec<_> = (1..5)
+        .map(|attempt| policy.backoff(attempt, &err))
+        .collect();
+    assert_eq!(
+        delays,
+        [100, 200, 300, 300].map(|ms| Some(Duration::from_millis(ms)))
+    );
+    assert_eq!(policy.backoff(5, &err), None);
+}
+
+#[test]
+fn jitter_shortens_the_delay() {
+    let policy = RetryPolicy {
+        jitter_percent: 50,
+        ..without_jitter()
+    };
+    for _ in 0..100 {
+        let delay = policy.backoff(2, &server_connect_error()).unwrap();
+        assert!(delay > Duration::from_millis(100) && delay <= Duration::from_millis(200));
+    }
+}
+
+#[test]
+fn only_configured_classes_are_retried() {
+    let policy = without_jitter();
+    let status = OpacityError::UnexpectedStatus(StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(RetryClass::of(&status), Some(RetryClass::ServerStatus));
+    assert_eq!(policy.backoff(1, &status), None);
+
+    let policy = RetryPolicy {
+        retry_on: vec![RetryClass::ServerStatus],
+        ..without_jitter()
+    };
+    assert!(policy.backoff(1, &status).is_some());
+    assert_eq!(policy.backoff(1, &server_connect_error()), None);
+
+    let not_found = OpacityError::UnexpectedStatus(StatusCode::NOT_FOUND);
+    assert_eq!(RetryClass::of(&not_found), None);
+}
+
+#[test]
+fn disabled_retries() {
+    assert_eq!(
+        RetryPolicy::none().backoff(1, &server_connect_error()),
+        None
+    );
+}
+
+#[tokio::test]
+async fn failures_after_the_request_was_sent_are_only_retried_for_safe_methods() {
+    let task = tokio::spawn(std::future::pending::<()>());
+    task.abort();
+    let err = OpacityError::ProverTask(task.await.unwrap_err());
+    assert_eq!(RetryClass::of(&err), Some(RetryClass::Session));
+
+    let policy = without_jitter();
+    for method in ["GET", "head", "OPTIONS", "TRACE"] {
+        assert_eq!(
+            policy.replay_backoff(1, &err, method),
+            policy.backoff(1, &err),
+            "{method}"
+        );
+    }
+    for method in ["POST", "PUT", "PATCH", "DELETE", "CONNECT"] {
+        assert_eq!(policy.replay_backoff(1, &err, method), None, "{method}");
+    }
+    assert_eq!(policy.replay_backoff(5, &err, "GET"), None);
+}
@@ -0,0 +1,78 @@
+use opacity::{TrustStore, TrustStoreError};
+use tempfile::TempDir;
+
+fn certificate_pem(name: &str) -> String {
+    rcgen::generate_simple_self_signed(vec![name.to_string()])
+        .unwrap()
+        .serialize_pem()
+        .unwrap()
+}
+
+#[test]
+fn every_certificate_of_a_bundle_is_trusted() {
+    let bundle = certificate_pem("a.example.com") + &certificate_pem("b.example.com");
+    let store = TrustStore::Pem(bundle.into_bytes());
+
+    assert_eq!(store.root_cert_store().unwrap().len(), 2);
+    assert!(store.server_root_cert_store().is_ok());
+}
+
+#[test]
+fn bundle_is_read_from_disk() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("ca.pem");
+    std::fs::write(&path, certificate_pem("a.example.com")).unwrap();
+
+    let store = TrustStore::PemFile(path);
+    assert_eq!(store.root_cert_store().unwrap().len(), 1);
+
+    let missing = TrustStore::PemFile(dir.path().join("missing.pem"));
+    let err = missing.root_cert_store().unwrap_err();
+    assert!(matches!(err, TrustStoreError::Io { .. }), "{err}");
+}
+
+#[test]
+fn bundle_without_certificates_is_refused() {
+    let key_only = rcgen::generate_simple_self_signed(vec!["a.example.com".to_string()])
+        .unwrap()
+        .serialize_private_key_pem();
+
+    for pem in [String::new(), key_only] {
+        let store = TrustStore::Pem(pem.into_bytes());
+        let err = store.root_cert_store().unwrap_err();
+        assert!(matches!(err, TrustStoreError::Empty), "{err}");
+        let err = store.server_root_cert_store().err().unwrap();
+        assert!(matches!(err, TrustStoreError::Empty), "{err}");
+    }
+}
+
+#[test]
+fn invalid_certificate_is_reported_by_index() {
+    let bundle = certificate_pem("a.example.com")
+        + "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+    let store = TrustStore::Pem(bundle.into_bytes());
+
+    let err = store.root_cert_store().unwrap_err();
+    assert!(
+        matches!(err, TrustStoreError::InvalidCertificate { index: 1, .. }),
+        "{err}"
+    );
+    let err = store.server_root_cert_store().err().unwrap();
+    assert!(
+        matches!(err, TrustStoreError::InvalidCertificate { index: 1, .. }),
+        "{err}"
+    );
+}
+
+#[test]
+fn malformed_pem_is_refused() {
+    let store =
+        TrustStore::Pem(b"-----BEGIN CERTIFICATE-----\n!!!\n-----END CERTIFICATE-----\n".to_vec());
+    let err = store.root_cert_store().unwrap_err();
+    assert!(matches!(err, TrustStoreError::Pem(_)), "{err}");
+}
+
+#[test]
+fn webpki_roots_are_not_empty() {
+    assert!(!TrustStore::WebPki.root_cert_store().unwrap().is_empty());
+}